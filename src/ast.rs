@@ -1,20 +1,42 @@
+use std::fmt;
+
 use crate::token::*;
+use crate::properties::*;
 use crate::value::Value;
 
+// name, type, initialiser and location of a variable in a 'var' statement
+pub type Declaration = (String, u32, Option<Box<ASTNode>>, (u32, u32));
+
+// offset, type, optional index and location of one variable in a 'read' statement
+pub type ReadTarget = (u32, u32, Option<Box<ASTNode>>, (u32, u32));
+
+#[allow(clippy::vec_box, reason = "child nodes are moved between lists and single boxes")]
 #[derive(Debug, Clone)]
 pub enum ASTNode {
     Toplevel {
-        funcdefs: Vec<Box<ASTNode>>
+        funcdefs: Vec<Box<ASTNode>>,
+
+        // test "name" { ... } items, as Funcdefs without parameters
+        tests: Vec<Box<ASTNode>>,
     },
     Funcdef {
         name: String,
-        params: Vec<(String, u8)>,
-        ret_type: u8,
+        params: Vec<(String, u32, (u32, u32))>,
+        ret_type: u32,
+        variadic: bool,
+
+        // file the function was defined in and where its name is, for
+        // diagnostics after parsing
+        source: usize,
+        loc: (u32, u32),
 
         body: Box<ASTNode>
     },
     Block {
         statements: Vec<Box<ASTNode>>,
+
+        // where each statement starts
+        locs: Vec<(u32, u32)>,
     },
     If {
         // branches.0 = conditions, branches.1 = statement
@@ -22,14 +44,21 @@ pub enum ASTNode {
 
         else_case: Option<Box<ASTNode>>,
     },
+    IfLet {
+        // if let some(x) = value; x lives at 'offset' inside the then case
+        offset: u32,
+        value: Box<ASTNode>,
+
+        then_case: Box<ASTNode>,
+        else_case: Option<Box<ASTNode>>,
+    },
     While {
         condition: Box<ASTNode>,
 
         statement: Box<ASTNode>,
     },
     VarDef {
-        tipe: u8,
-        names: Vec<String>,
+        vars: Vec<Declaration>,
     },
     Call {
         name: String,
@@ -39,17 +68,37 @@ pub enum ASTNode {
         offset: u32,
         index: Option<Box<ASTNode>>,
 
-        // does 'array' preceed the assignment expression
-        is_array: bool,
+        // 'let a = array n' fills the new array with this, rhs is then n
+        fill: Option<Value>,
         rhs: Box<ASTNode>,
+        loc: (u32, u32),
+    },
+    Destructure {
+        // None for '_' elements
+        offsets: Vec<Option<u32>>,
+        rhs: Box<ASTNode>,
+        loc: (u32, u32),
     },
     Read {
-        offset: u32,
-        tipe: u8,
-        index: Option<Box<ASTNode>>,
+        // One word of input each, an array without an index takes one per element.
+        targets: Vec<ReadTarget>,
+
+        // read(x)?; returns bad input as an error instead of aborting
+        propagate: bool,
+    },
+    Assert {
+        condition: Box<ASTNode>,
+        message: Option<Box<ASTNode>>,
+
+        // reported when the assertion fails
+        source: usize,
+        loc: (u32, u32),
     },
     Print {
         items: Vec<Box<ASTNode>>,
+
+        // eprint(...) writes to stderr instead of stdout
+        stderr: bool,
     },
     Return {
         expr: Option<Box<ASTNode>>,
@@ -66,16 +115,211 @@ pub enum ASTNode {
     GetVar {
         name: String,
         offset: u32,
+        loc: (u32, u32),
     },
     GetIndex {
         offset: u32,
         idx: Box<ASTNode>, // expr node
+        loc: (u32, u32),
     },
-    SetIndex {
-        offset: u32,
-        idx: Box<ASTNode>,
+    Some {
+        value: Box<ASTNode>,
+    },
+    Ok {
+        value: Box<ASTNode>,
+    },
+    Err {
+        message: Box<ASTNode>,
+    },
+    Discard {
+        value: Box<ASTNode>,
+    },
+    Try {
+        value: Box<ASTNode>,
+
+        // false inside main, where an error ends the program instead
+        propagate: bool,
+    },
+    Tuple {
+        items: Vec<Box<ASTNode>>,
+    },
+    Conditional {
+        // if condition then then_value else else_value, only one side is evaluated
+        condition: Box<ASTNode>,
+        then_value: Box<ASTNode>,
+        else_value: Box<ASTNode>,
+    },
+    Inlined {
+        // A call to a small function with the body pasted in. The arguments
+        // go into the caller's frame from base onwards, which is where the
+        // body's variables have been moved to.
+        name: String,
+        base: u32,
+        args: Vec<Box<ASTNode>>,
+        body: Box<ASTNode>,
     },
     Value {
         val: Value,
     }
-}
\ No newline at end of file
+}
+// 'rick ast' output: one node per line, children indented below their
+// parent. Slots in the function's frame are written as @n.
+impl fmt::Display for ASTNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_node(f, self, 0, "")
+    }
+}
+
+fn write_node(f: &mut fmt::Formatter, node: &ASTNode, depth: usize, label: &str) -> fmt::Result {
+    let line: String = match node {
+        ASTNode::Toplevel { funcdefs, tests } => {
+            for (i, fdef) in funcdefs.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                write_node(f, fdef, depth, "")?;
+            }
+            for test in tests {
+                if let ASTNode::Funcdef { name, body, .. } = &**test {
+                    writeln!(f)?;
+                    writeln!(f, "{}test {:?}", "  ".repeat(depth), name)?;
+                    write_node(f, body, depth + 1, "")?;
+                }
+            }
+            return Ok(());
+        },
+        ASTNode::Funcdef { name, params, ret_type, variadic, body, .. } => {
+            let mut list: Vec<String> = params.iter().map(|(p, t, _)| format!("{} {}", type_string(*t), p)).collect();
+            if let (true, Some((p, t, _))) = (*variadic, params.last()) {
+                list.pop();
+                list.push(format!("{} ... {}", type_string(*t & !ARRAY), p));
+            }
+
+            let ret: String = match *ret_type & !FUNC {
+                NONE => String::new(),
+                t => format!(" {}", type_string(t)),
+            };
+            writeln!(f, "{}{}func {}({}){}", "  ".repeat(depth), label, name, list.join(", "), ret)?;
+            return write_node(f, body, depth + 1, "");
+        },
+        ASTNode::Block { .. } => "block".into(),
+        ASTNode::If { .. } => "if".into(),
+        ASTNode::IfLet { offset, .. } => format!("if let some(@{})", offset),
+        ASTNode::While { .. } => "while".into(),
+        ASTNode::VarDef { .. } => "var".into(),
+        ASTNode::Call { name, .. } => format!("call {}", name),
+        ASTNode::Let { offset, fill: Some(_), .. } => format!("let @{} = array", offset),
+        ASTNode::Let { offset, .. } => format!("let @{}", offset),
+        ASTNode::Destructure { offsets, .. } => {
+            let names: Vec<String> = offsets.iter().map(|o| o.map_or("_".into(), |o| format!("@{}", o))).collect();
+            format!("let ({})", names.join(", "))
+        },
+        ASTNode::Read { propagate, .. } => if *propagate { "read ?".into() } else { "read".into() },
+        ASTNode::Assert { .. } => "assert".into(),
+        ASTNode::Print { stderr, .. } => if *stderr { "eprint".into() } else { "print".into() },
+        ASTNode::Return { .. } => "return".into(),
+        ASTNode::UnaryOp { op, .. } => format!("unary {}", op),
+        ASTNode::BinaryOp { op, .. } => format!("binary {}", op),
+        ASTNode::GetVar { name, offset, .. } => format!("{} @{}", name, offset),
+        ASTNode::GetIndex { offset, .. } => format!("index @{}", offset),
+        ASTNode::Some { .. } => "some".into(),
+        ASTNode::Ok { .. } => "ok".into(),
+        ASTNode::Err { .. } => "err".into(),
+        ASTNode::Discard { .. } => "discard".into(),
+        ASTNode::Try { propagate, .. } => if *propagate { "try ?".into() } else { "try, error ends the program".into() },
+        ASTNode::Tuple { .. } => "tuple".into(),
+        ASTNode::Conditional { .. } => "if then else".into(),
+        ASTNode::Inlined { name, base, .. } => format!("inlined {} @{}", name, base),
+        ASTNode::Value { val: Value::String(s) } => format!("{:?}", s),
+        ASTNode::Value { val: Value::Char(c) } => format!("{:?}", c),
+        ASTNode::Value { val } => val.to_string(),
+    };
+
+    writeln!(f, "{}{}{}", "  ".repeat(depth), label, line)?;
+
+    let depth: usize = depth + 1;
+    match node {
+        ASTNode::Block { statements, .. } => {
+            for statement in statements {
+                write_node(f, statement, depth, "")?;
+            }
+        },
+        ASTNode::If { branches, else_case } => {
+            for (i, (cond, body)) in branches.iter().enumerate() {
+                write_node(f, cond, depth, if i == 0 { "cond: " } else { "elif: " })?;
+                write_node(f, body, depth, "then: ")?;
+            }
+            if let Some(else_case) = else_case {
+                write_node(f, else_case, depth, "else: ")?;
+            }
+        },
+        ASTNode::IfLet { value, then_case, else_case, .. } => {
+            write_node(f, value, depth, "value: ")?;
+            write_node(f, then_case, depth, "then: ")?;
+            if let Some(else_case) = else_case {
+                write_node(f, else_case, depth, "else: ")?;
+            }
+        },
+        ASTNode::While { condition, statement } => {
+            write_node(f, condition, depth, "cond: ")?;
+            write_node(f, statement, depth, "body: ")?;
+        },
+        ASTNode::VarDef { vars } => {
+            for (name, tipe, init, _) in vars {
+                writeln!(f, "{}{} {}", "  ".repeat(depth), type_string(*tipe), name)?;
+                if let Some(init) = init {
+                    write_node(f, init, depth + 1, "= ")?;
+                }
+            }
+        },
+        ASTNode::Let { index, fill, rhs, .. } => {
+            if let Some(index) = index {
+                write_node(f, index, depth, "index: ")?;
+            }
+            write_node(f, rhs, depth, if fill.is_some() { "size: " } else { "value: " })?;
+        },
+        ASTNode::Destructure { rhs, .. } => write_node(f, rhs, depth, "value: ")?,
+        ASTNode::Read { targets, .. } => {
+            for (offset, tipe, index, _) in targets {
+                writeln!(f, "{}{} @{}", "  ".repeat(depth), type_string(*tipe), offset)?;
+                if let Some(index) = index {
+                    write_node(f, index, depth + 1, "index: ")?;
+                }
+            }
+        },
+        ASTNode::Assert { condition, message, .. } => {
+            write_node(f, condition, depth, "cond: ")?;
+            if let Some(message) = message {
+                write_node(f, message, depth, "message: ")?;
+            }
+        },
+        ASTNode::Conditional { condition, then_value, else_value } => {
+            write_node(f, condition, depth, "cond: ")?;
+            write_node(f, then_value, depth, "then: ")?;
+            write_node(f, else_value, depth, "else: ")?;
+        },
+        ASTNode::Inlined { args, body, .. } => {
+            for arg in args {
+                write_node(f, arg, depth, "arg: ")?;
+            }
+            write_node(f, body, depth, "body: ")?;
+        },
+        ASTNode::Call { args: items, .. } | ASTNode::Print { items, .. } | ASTNode::Tuple { items } => {
+            for item in items {
+                write_node(f, item, depth, "")?;
+            }
+        },
+        ASTNode::Return { expr: Some(value) } | ASTNode::UnaryOp { value, .. } | ASTNode::Some { value }
+            | ASTNode::Ok { value } | ASTNode::Err { message: value } | ASTNode::Discard { value }
+            | ASTNode::Try { value, .. } | ASTNode::GetIndex { idx: value, .. } => {
+            write_node(f, value, depth, "")?;
+        },
+        ASTNode::BinaryOp { lhs, rhs, .. } => {
+            write_node(f, lhs, depth, "")?;
+            write_node(f, rhs, depth, "")?;
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
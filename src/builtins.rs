@@ -0,0 +1,499 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::properties::*;
+use crate::value::{self, Value};
+use crate::walker::Walker;
+
+// Functions implemented by the interpreter itself. The parser type checks
+// calls to them like any other function, the walker runs 'call' instead of
+// a function body. An Err is raised as a runtime error.
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [(&'static str, u32)],
+    pub ret_type: u32,
+    pub call: fn(&mut Walker, Vec<Value>) -> Result<Value, String>,
+}
+
+impl Builtin {
+    pub fn properties(&self) -> Properties {
+        let mut tipe: u32 = NONE;
+
+        if self.ret_type != NONE {
+            tipe = self.ret_type | FUNC;
+        }
+
+        Properties {
+            tipe,
+            offset: None,
+            params: self.params.iter().map(|(name, t)| (name.to_string(), *t)).collect(),
+            defaults: vec![None; self.params.len()],
+            param_locs: vec![],
+            variadic: false,
+            public: true,
+        }
+    }
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin { name: "len", params: &[("x", SIZED)], ret_type: INTEGER, call: len },
+    Builtin { name: "ord", params: &[("c", CHAR)], ret_type: INTEGER, call: ord },
+    Builtin { name: "chr", params: &[("code", INTEGER)], ret_type: CHAR, call: chr },
+    Builtin { name: "chars", params: &[("s", STRING)], ret_type: CHAR | ARRAY, call: chars },
+    Builtin { name: "from_chars", params: &[("cs", CHAR | ARRAY)], ret_type: STRING, call: from_chars },
+    Builtin { name: "readline", params: &[], ret_type: STRING, call: readline },
+    Builtin { name: "eof", params: &[], ret_type: BOOLEAN, call: eof },
+    Builtin { name: "open", params: &[("path", STRING), ("mode", STRING)], ret_type: RESULT | FILE, call: open },
+    Builtin { name: "read_line", params: &[("h", FILE)], ret_type: RESULT | OPTIONAL | STRING, call: read_line },
+    Builtin { name: "write", params: &[("h", FILE), ("s", STRING)], ret_type: RESULT | INTEGER, call: write },
+    Builtin { name: "close", params: &[("h", FILE)], ret_type: NONE, call: close },
+    Builtin { name: "read_file", params: &[("path", STRING)], ret_type: RESULT | STRING, call: read_file },
+    Builtin { name: "write_file", params: &[("path", STRING), ("s", STRING)], ret_type: RESULT | INTEGER, call: write_file },
+    Builtin { name: "exists", params: &[("path", STRING)], ret_type: BOOLEAN, call: exists },
+    Builtin { name: "getenv", params: &[("name", STRING)], ret_type: OPTIONAL | STRING, call: getenv },
+    Builtin { name: "exit", params: &[("code", INTEGER)], ret_type: NONE, call: exit },
+    Builtin { name: "now_ms", params: &[], ret_type: INTEGER, call: now_ms },
+    Builtin { name: "uptime_ms", params: &[], ret_type: INTEGER, call: now_ms },
+    Builtin { name: "monotonic_ns", params: &[], ret_type: INTEGER_PAIR, call: monotonic_ns },
+    Builtin { name: "sleep", params: &[("ms", INTEGER)], ret_type: NONE, call: sleep },
+    Builtin { name: "date", params: &[], ret_type: STRING, call: date },
+    Builtin { name: "rand_int", params: &[("lo", INTEGER), ("hi", INTEGER)], ret_type: INTEGER, call: rand_int },
+    Builtin { name: "rand_float", params: &[], ret_type: FLOAT, call: rand_float },
+    Builtin { name: "seed", params: &[("n", INTEGER)], ret_type: NONE, call: seed },
+    Builtin { name: "pi", params: &[], ret_type: FLOAT, call: pi },
+    Builtin { name: "e", params: &[], ret_type: FLOAT, call: e },
+    Builtin { name: "sqrt", params: &[("x", NUMERIC)], ret_type: FLOAT, call: sqrt },
+    Builtin { name: "sin", params: &[("x", NUMERIC)], ret_type: FLOAT, call: sin },
+    Builtin { name: "cos", params: &[("x", NUMERIC)], ret_type: FLOAT, call: cos },
+    Builtin { name: "tan", params: &[("x", NUMERIC)], ret_type: FLOAT, call: tan },
+    Builtin { name: "atan2", params: &[("y", NUMERIC), ("x", NUMERIC)], ret_type: FLOAT, call: atan2 },
+    Builtin { name: "pow", params: &[("x", NUMERIC), ("y", NUMERIC)], ret_type: FLOAT, call: pow },
+    Builtin { name: "exp", params: &[("x", NUMERIC)], ret_type: FLOAT, call: exp },
+    Builtin { name: "ln", params: &[("x", NUMERIC)], ret_type: FLOAT, call: ln },
+    Builtin { name: "log10", params: &[("x", NUMERIC)], ret_type: FLOAT, call: log10 },
+    Builtin { name: "floor", params: &[("x", NUMERIC)], ret_type: INTEGER, call: floor },
+    Builtin { name: "ceil", params: &[("x", NUMERIC)], ret_type: INTEGER, call: ceil },
+    Builtin { name: "abs", params: &[("x", NUMERIC)], ret_type: NUMERIC, call: abs },
+    Builtin { name: "min", params: &[("a", NUMERIC), ("b", NUMERIC)], ret_type: NUMERIC, call: min },
+    Builtin { name: "max", params: &[("a", NUMERIC), ("b", NUMERIC)], ret_type: NUMERIC, call: max },
+    Builtin { name: "clamp", params: &[("x", NUMERIC), ("lo", NUMERIC), ("hi", NUMERIC)], ret_type: NUMERIC, call: clamp },
+    Builtin { name: "gcd", params: &[("a", INTEGER), ("b", INTEGER)], ret_type: INTEGER, call: gcd },
+    Builtin { name: "parse_int", params: &[("s", STRING)], ret_type: OPTIONAL | INTEGER, call: parse_int },
+    Builtin { name: "parse_int_radix", params: &[("s", STRING), ("base", INTEGER)], ret_type: OPTIONAL | INTEGER, call: parse_int_radix },
+    Builtin { name: "parse_float", params: &[("s", STRING)], ret_type: OPTIONAL | FLOAT, call: parse_float },
+    Builtin { name: "parse_bool", params: &[("s", STRING)], ret_type: OPTIONAL | BOOLEAN, call: parse_bool },
+    Builtin { name: "to_string", params: &[("x", ANY)], ret_type: STRING, call: to_string },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+// Strings are counted in characters, the same way they are indexed.
+fn len(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let n: usize = match &args[0] {
+        Value::Array(items) => items.len(),
+        Value::String(s) => s.chars().count(),
+        _ => panic!("Typechecking fail"),
+    };
+
+    Ok(Value::Integer(n as i32))
+}
+
+fn ord(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Char(c) => Ok(Value::Integer(*c as i32)),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn chr(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let code: i32 = args[0].force_int();
+
+    match char::from_u32(code as u32) {
+        Some(c) if code >= 0 => Ok(Value::Char(c)),
+        _ => Err(format!("invalid character code {}", code)),
+    }
+}
+
+fn chars(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(s) => Ok(Value::Array(s.chars().map(Value::Char).collect())),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn from_chars(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let mut out: String = String::new();
+
+    if let Value::Array(items) = &args[0] {
+        for item in items {
+            if let Value::Char(c) = item {
+                out.push(*c);
+            }
+        }
+    }
+
+    Ok(Value::String(out))
+}
+
+fn readline(walker: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    match walker.input().line() {
+        Some(line) => Ok(Value::String(line)),
+        None => Err("unexpected end of input".into()),
+    }
+}
+
+// True once only whitespace is left on stdin.
+fn eof(walker: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Boolean(walker.input().at_end()))
+}
+
+// File operations hand failures back to the program as err(...), only
+// misusing a handle is a runtime error.
+fn to_result(result: Result<Value, String>) -> Result<Value, String> {
+    Ok(Value::Result(result.map(Box::new)))
+}
+
+fn handle(value: &Value) -> usize {
+    match value {
+        Value::File(h) => *h,
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn open(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let path: String = args[0].to_string();
+    let mode: String = args[1].to_string();
+
+    to_result(walker.files().open(&path, &mode).map(Value::File))
+}
+
+fn read_line(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let line = walker.files().read_line(handle(&args[0]));
+
+    to_result(line.map(|l| Value::Optional(l.map(|s| Box::new(Value::String(s))))))
+}
+
+// Gives back the number of bytes written.
+fn write(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let written = walker.files().write(handle(&args[0]), &args[1].to_string());
+
+    to_result(written.map(|n| Value::Integer(n as i32)))
+}
+
+fn close(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    if !walker.files().close(handle(&args[0])) {
+        return Err("file is already closed".into());
+    }
+
+    Ok(Value::None)
+}
+
+fn read_file(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let path: String = args[0].to_string();
+
+    to_result(fs::read_to_string(&path).map(Value::String).map_err(|e| format!("cannot read '{}': {}", path, e)))
+}
+
+fn write_file(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let path: String = args[0].to_string();
+    let text: String = args[1].to_string();
+
+    to_result(fs::write(&path, &text).map(|_| Value::Integer(text.len() as i32)).map_err(|e| format!("cannot write '{}': {}", path, e)))
+}
+
+fn exists(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Boolean(Path::new(&args[0].to_string()).exists()))
+}
+
+// none when the variable isn't set or isn't valid unicode
+fn getenv(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let value: Option<String> = env::var(args[0].to_string()).ok();
+
+    Ok(Value::Optional(value.map(|v| Box::new(Value::String(v)))))
+}
+
+// Unwinds like a runtime error, walk() then returns the code.
+fn exit(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    walker.exit(args[0].force_int());
+
+    Ok(Value::None)
+}
+
+// Milliseconds since the program started. It is a runtime error after about
+// 24.8 days, when the count no longer fits in an integer.
+fn now_ms(walker: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    let ms: u128 = walker.uptime().as_millis();
+
+    i32::try_from(ms).map(Value::Integer).map_err(|_| format!("uptime of {}ms does not fit in an integer", ms))
+}
+
+// Nanoseconds since the program started, split into whole seconds and the
+// nanoseconds within that second. A plain nanosecond count would no longer
+// fit in an integer after 2.1 seconds.
+fn monotonic_ns(walker: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    let uptime: Duration = walker.uptime();
+
+    match i32::try_from(uptime.as_secs()) {
+        Ok(secs) => Ok(Value::Tuple(vec![Value::Integer(secs), Value::Integer(uptime.subsec_nanos() as i32)])),
+        Err(_) => Err(format!("uptime of {}s does not fit in an integer", uptime.as_secs())),
+    }
+}
+
+fn sleep(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let ms: i32 = args[0].force_int();
+
+    if ms < 0 {
+        return Err(format!("cannot sleep for {}ms", ms));
+    }
+    thread::sleep(Duration::from_millis(ms as u64));
+
+    Ok(Value::None)
+}
+
+// The current UTC date and time as YYYY-MM-DD HH:MM:SS.
+fn date(_: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    let secs: u64 = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    Ok(Value::String(format_date(secs)))
+}
+
+// Seconds since the Unix epoch as YYYY-MM-DD HH:MM:SS in UTC.
+fn format_date(secs: u64) -> String {
+    let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+
+    // days since 1970-01-01 to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z: i64 = days + 719468;
+    let era: i64 = z.div_euclid(146097);
+    let doe: i64 = z - era * 146097;
+    let yoe: i64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy: i64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: i64 = (5 * doy + 2) / 153;
+    let day: i64 = doy - (153 * mp + 2) / 5 + 1;
+    let month: i64 = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year: i64 = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+fn rand_int(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let (lo, hi) = (args[0].force_int(), args[1].force_int());
+
+    if lo > hi {
+        return Err(format!("rand_int({}, {}) has an empty range", lo, hi));
+    }
+
+    Ok(Value::Integer(walker.rng().range(lo, hi)))
+}
+
+fn rand_float(walker: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(walker.rng().float()))
+}
+
+// seed(n) gives the same numbers as running with --seed n
+fn seed(walker: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    walker.seed(args[0].force_int() as i64 as u64);
+
+    Ok(Value::None)
+}
+
+// Integer arguments to the float functions are converted first.
+fn to_float(value: &Value) -> f32 {
+    match value {
+        Value::Integer(i) => *i as f32,
+        Value::Float(f) => *f,
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn pi(_: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(std::f32::consts::PI))
+}
+
+fn e(_: &mut Walker, _: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(std::f32::consts::E))
+}
+
+// Out of domain arguments give NaN or infinity like they do in the hardware.
+fn sqrt(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).sqrt()))
+}
+
+fn sin(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).sin()))
+}
+
+fn cos(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).cos()))
+}
+
+fn tan(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).tan()))
+}
+
+fn atan2(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).atan2(to_float(&args[1]))))
+}
+
+fn pow(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).powf(to_float(&args[1]))))
+}
+
+fn exp(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).exp()))
+}
+
+fn ln(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).ln()))
+}
+
+fn log10(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Float(to_float(&args[0]).log10()))
+}
+
+// floor and ceil are how a float becomes an integer, so one that doesn't
+// fit is a runtime error.
+fn to_integer(name: &str, x: f32) -> Result<Value, String> {
+    if x.is_finite() && x >= i32::MIN as f32 && x < i32::MAX as f32 {
+        Ok(Value::Integer(x as i32))
+    } else {
+        Err(format!("{}: {} does not fit in an integer", name, x))
+    }
+}
+
+fn floor(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    to_integer("floor", to_float(&args[0]).floor())
+}
+
+fn ceil(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    to_integer("ceil", to_float(&args[0]).ceil())
+}
+
+fn abs(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::Integer(i) => i.checked_abs().map(Value::Integer).ok_or_else(|| format!("abs({}) overflows", i)),
+        Value::Float(f) => Ok(Value::Float(f.abs())),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+// Integers are turned into floats when any of the arguments is a float,
+// matching the return type the parser gave the call.
+fn promote(args: Vec<Value>) -> Vec<Value> {
+    if args.iter().any(|arg| matches!(arg, Value::Float(_))) {
+        args.iter().map(|arg| Value::Float(to_float(arg))).collect()
+    } else {
+        args
+    }
+}
+
+fn min(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let args = promote(args);
+    match (&args[0], &args[1]) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(*a.min(b))),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.min(*b))),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn max(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let args = promote(args);
+    match (&args[0], &args[1]) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(*a.max(b))),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.max(*b))),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn clamp(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let args = promote(args);
+    match (&args[0], &args[1], &args[2]) {
+        (Value::Integer(x), Value::Integer(lo), Value::Integer(hi)) if lo <= hi => Ok(Value::Integer(*x.clamp(lo, hi))),
+        (Value::Float(x), Value::Float(lo), Value::Float(hi)) if lo <= hi => Ok(Value::Float(x.clamp(*lo, *hi))),
+        (_, lo, hi) => Err(format!("clamp: lower bound {} is above upper bound {}", lo, hi)),
+    }
+}
+
+// Always non-negative, gcd(0, 0) is 0.
+fn gcd(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let (mut a, mut b) = (args[0].force_int().unsigned_abs(), args[1].force_int().unsigned_abs());
+
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    i32::try_from(a).map(Value::Integer).map_err(|_| format!("gcd({}, {}) overflows", args[0], args[1]))
+}
+
+// The parse functions give none for anything that isn't a valid number or
+// boolean, the same text read() would reject.
+fn to_optional<T>(parsed: Option<T>, wrap: fn(T) -> Value) -> Result<Value, String> {
+    Ok(Value::Optional(parsed.map(|v| Box::new(wrap(v)))))
+}
+
+fn parse_int(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(s) => to_optional(value::parse_int(s, 10), Value::Integer),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+// Digits past 9 are letters, so bases go up to 36.
+fn parse_int_radix(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    let base: i32 = args[1].force_int();
+
+    if !(2..=36).contains(&base) {
+        return Err(format!("parse_int_radix: base {} is not between 2 and 36", base));
+    }
+
+    match &args[0] {
+        Value::String(s) => to_optional(value::parse_int(s, base as u32), Value::Integer),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn parse_float(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(s) => to_optional(value::parse_float(s), Value::Float),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+fn parse_bool(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(s) => to_optional(value::parse_bool(s), Value::Boolean),
+        _ => panic!("Typechecking fail"),
+    }
+}
+
+// The same text print would write.
+fn to_string(_: &mut Walker, args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(args[0].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_from_epoch_seconds() {
+        assert_eq!(format_date(0), "1970-01-01 00:00:00");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14 22:13:20");
+        assert_eq!(format_date(2_147_483_647), "2038-01-19 03:14:07");
+        assert_eq!(format_date(253_402_300_799), "9999-12-31 23:59:59");
+    }
+
+    #[test]
+    fn date_leap_years() {
+        assert_eq!(format_date(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_date(951_868_799), "2000-02-29 23:59:59");
+        assert_eq!(format_date(4_107_456_000), "2100-02-28 00:00:00");
+        assert_eq!(format_date(4_107_542_400), "2100-03-01 00:00:00");
+    }
+}
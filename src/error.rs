@@ -1,25 +1,152 @@
+use std::fmt;
 use std::sync::Mutex;
 
 use crate::token::*;
+use crate::properties::type_string;
 
 // XXX: Using mutexes is inelegant. It is just to keep the compiler happy.
-pub static SOURCE_NAME: Mutex<String> = Mutex::new(String::new());
+// Every file that gets scanned is registered here, SOURCE_ID picks the one
+// errors are currently being reported against.
+pub static SOURCE_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+pub static SOURCE_ID: Mutex<usize> = Mutex::new(0);
 
 pub static SOURCE_LINE: Mutex<u32> = Mutex::new(0);
 pub static SOURCE_COL: Mutex<u32> = Mutex::new(0);
 
-pub static COLUMN_NUM: Mutex<u32> = Mutex::new(0);
+pub static COLUMN_NUM: Mutex<u32> = Mutex::new(1);
 
 pub enum RickError {
     UnclosedString,
+    MalformedChar,
     NumberParseFailure,
     IllegalCharacter(u8),
     IllegalEscapeCode(char),
-    IdentifierTooLong,
-    NonPrintableInString(u8),
-    MalformedFuncdef(String),
     Expected(Token, Token),
     MissingTypeSpecifier,
+    ModuleNotFound(String),
+    ImportCycle(String),
+    ModuleNameClash(String),
+    UnknownModule(String),
+    UndefinedFunction(String),
+    PrivateFunction(String),
+    Redefinition(String),
+    TypeMismatch(u32, u32),
+    DestructureMismatch(u32, usize),
+    NotOptional(u32),
+    NestedOptional(u32),
+    NotResult(u32),
+    NestedResult(u32),
+    TryOutsideResult(String),
+    ExpectedLiteral(Token),
+    RequiredAfterDefault(String),
+    TooManyArguments(String),
+    MissingArgument(String, String),
+    UnknownParameter(String, String),
+    DuplicateArgument(String, String),
+    PositionalAfterNamed(String),
+    ArgumentType(String, String, u32, u32),
+    NestedArray(u32),
+    BuiltinRedefinition(String),
+    NoDefaultElement(u32),
+    BranchMismatch(u32, u32),
+    CannotInfer(u32),
+    UnassignedVariable(String),
+    MissingReturn(String),
+    DuplicateTest(String),
+    MissingMain,
+    MainParameters,
+    MainReturnType(u32),
+}
+
+pub enum RickWarning {
+    UnreachableCode,
+    UnusedVariable(String),
+    UnusedParameter(String, String),
+    UnusedFunction(String),
+    UnusedAssignment(String),
+}
+
+// Names for -W / -A on the command line, in the same order as RickWarning.
+pub const WARNING_NAMES: [&str; 5] = [
+    "unreachable-code",
+    "unused-variable",
+    "unused-parameter",
+    "unused-function",
+    "unused-assignment",
+];
+
+// Every warning is on unless it is allowed with -A.
+pub static ALLOWED_WARNINGS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+impl fmt::Display for RickError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RickError::UnclosedString => write!(f, "string not closed"),
+            RickError::NumberParseFailure => write!(f, "failed to parse number literal"),
+            RickError::IllegalCharacter(d) => write!(f, "illegal character (ASCII #{})", d),
+            RickError::MalformedChar => write!(f, "character literal must hold exactly one character"),
+            RickError::IllegalEscapeCode(c) => write!(f, "illegal escape code '\\{}'", c),
+            RickError::Expected(found, expected) => write!(f, "expected '{}', found'{}'", expected, found),
+            RickError::MissingTypeSpecifier => write!(f, "expected type specifier"),
+            RickError::ModuleNotFound(m) => write!(f, "module '{}' not found", m),
+            RickError::ImportCycle(chain) => write!(f, "import cycle: {}", chain),
+            RickError::ModuleNameClash(m) => write!(f, "two different files are both imported as module '{}'", m),
+            RickError::UnknownModule(m) => write!(f, "module '{}' is not imported here", m),
+            RickError::UndefinedFunction(id) => write!(f, "function '{}' does not exist", id),
+            RickError::PrivateFunction(id) => write!(f, "function '{}' is not public", id),
+            RickError::Redefinition(id) => write!(f, "function '{}' is defined more than once", id),
+            RickError::TypeMismatch(expected, found) => write!(f, "expected {} type, found {} type", type_string(*expected), type_string(*found)),
+            RickError::DestructureMismatch(found, count) => write!(f, "cannot destructure {} into {} variables", type_string(*found), count),
+            RickError::NotOptional(found) => write!(f, "expected optional type, found {} type", type_string(*found)),
+            RickError::NestedOptional(found) => write!(f, "cannot wrap {} type in an optional", type_string(*found)),
+            RickError::NotResult(found) => write!(f, "expected result type, found {} type", type_string(*found)),
+            RickError::NestedResult(found) => write!(f, "cannot wrap {} type in another result", type_string(*found)),
+            RickError::TryOutsideResult(func) => write!(f, "'?' used in '{}', which does not return a result", func),
+            RickError::ExpectedLiteral(found) => write!(f, "expected a literal default value, found '{}'", found),
+            RickError::RequiredAfterDefault(param) => write!(f, "parameter '{}' needs a default value, it follows one that has a default", param),
+            RickError::TooManyArguments(func) => write!(f, "too many arguments for '{}'", func),
+            RickError::MissingArgument(func, param) => write!(f, "missing argument '{}' for '{}'", param, func),
+            RickError::UnknownParameter(func, param) => write!(f, "'{}' has no parameter named '{}'", func, param),
+            RickError::DuplicateArgument(func, param) => write!(f, "argument '{}' for '{}' is given more than once", param, func),
+            RickError::PositionalAfterNamed(func) => write!(f, "positional argument after named arguments in call to '{}'", func),
+            RickError::ArgumentType(func, param, expected, found) => write!(f, "argument '{}' of '{}' expects {} type, found {} type", param, func, type_string(*expected), type_string(*found)),
+            RickError::NestedArray(found) => write!(f, "cannot make an array of {} type", type_string(*found)),
+            RickError::BuiltinRedefinition(id) => write!(f, "'{}' is a builtin function and cannot be redefined", id),
+            RickError::NoDefaultElement(t) => write!(f, "'array n' cannot make {} elements, they have no default value", type_string(*t)),
+            RickError::MissingMain => write!(f, "program has no 'main' function"),
+            RickError::MainParameters => write!(f, "main must be declared as main(), main(string array args) or main(integer argc, string array args)"),
+            RickError::MainReturnType(found) => write!(f, "main must return integer, result integer or nothing, found {} type", type_string(*found)),
+            RickError::DuplicateTest(name) => write!(f, "there is more than one test called \"{}\"", name),
+            RickError::MissingReturn(id) => write!(f, "function '{}' can reach its end without returning a value", id),
+            RickError::UnassignedVariable(id) => write!(f, "variable '{}' may be used before it is assigned", id),
+            RickError::CannotInfer(found) => write!(f, "cannot infer a variable type from {} type", type_string(*found)),
+            RickError::BranchMismatch(then_type, else_type) => write!(f, "if expression gives {} type in one branch and {} type in the other", type_string(*then_type), type_string(*else_type)),
+        }
+    }
+}
+
+impl RickWarning {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RickWarning::UnreachableCode => WARNING_NAMES[0],
+            RickWarning::UnusedVariable(_) => WARNING_NAMES[1],
+            RickWarning::UnusedParameter(_, _) => WARNING_NAMES[2],
+            RickWarning::UnusedFunction(_) => WARNING_NAMES[3],
+            RickWarning::UnusedAssignment(_) => WARNING_NAMES[4],
+        }
+    }
+}
+
+impl fmt::Display for RickWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RickWarning::UnreachableCode => write!(f, "unreachable code"),
+            RickWarning::UnusedVariable(id) => write!(f, "variable '{}' is never read", id),
+            RickWarning::UnusedParameter(func, param) => write!(f, "parameter '{}' of '{}' is never used", param, func),
+            RickWarning::UnusedFunction(id) => write!(f, "function '{}' is never called from main", id),
+            RickWarning::UnusedAssignment(id) => write!(f, "value assigned to '{}' is overwritten before it is read", id),
+        }
+    }
 }
 
 pub fn inc_col() {
@@ -44,8 +171,34 @@ pub fn save_loc() {
     *SOURCE_COL.lock().unwrap() = *COLUMN_NUM.lock().unwrap();
 }
 
-pub fn set_source(filename: String) {
-    *SOURCE_NAME.lock().unwrap() = filename;
+// Registers a new source file, makes it current and rewinds the scanner position.
+pub fn add_source(filename: String) -> usize {
+    let mut names = SOURCE_NAMES.lock().unwrap();
+    names.push(filename);
+
+    let id: usize = names.len() - 1;
+    *SOURCE_ID.lock().unwrap() = id;
+
+    set_loc(1, 1);
+    *COLUMN_NUM.lock().unwrap() = 1;
+
+    id
+}
+
+pub fn set_source(id: usize) {
+    *SOURCE_ID.lock().unwrap() = id;
+}
+
+pub fn source_name() -> String {
+    let id: usize = *SOURCE_ID.lock().unwrap();
+
+    source_name_of(id)
+}
+
+pub fn source_name_of(id: usize) -> String {
+    let names = SOURCE_NAMES.lock().unwrap();
+
+    names.get(id).cloned().unwrap_or_default()
 }
 
 pub fn get_loc() -> (u32, u32) {
@@ -56,71 +209,54 @@ pub fn get_loc() -> (u32, u32) {
 }
 
 pub fn report_err(reason: RickError) -> ! {
-    match reason {
-        RickError::UnclosedString => { eprintln!("rick: {}: {}:{} error: string not closed",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap());
-        },
-
-        RickError::NumberParseFailure => { eprintln!("rick: {}: {}:{} error: failed to parse number literal",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap());
-        },
-
-        RickError::IllegalCharacter(d) => { eprintln!("rick: {}: {}:{} error: illegal character (ASCII #{})",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap(),
-            d);
-        },
-
-        RickError::IllegalEscapeCode(c) => { eprintln!("rick: {}: {}:{} error: illegal escape code '\\{}' in string",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap(),
-            c);
-        },
-
-        RickError::IdentifierTooLong => { eprintln!("rick: {}: {}:{} error: identifier too long",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap());
-        },
-
-        RickError::NonPrintableInString(d) => { eprintln!("rick: {}: {}:{} error: non-printable character (ASCII #{}) in string",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap(),
-            d);
-        },
-
-        RickError::MalformedFuncdef(s) => { eprintln!("rick: {}: {}:{} error: malformed funcdef: {}",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap(),
-            s);
-        },
-
-        RickError::Expected(found, expected) => { eprintln!("rick: {}: {}:{} error: expected '{}', found'{}'",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap(),
-            expected,
-            found);
-        },
-
-        RickError::MissingTypeSpecifier => { eprintln!("rick: {}: {}:{} error: expected type specifier",
-            SOURCE_NAME.lock().unwrap(),
-            SOURCE_LINE.lock().unwrap(),
-            SOURCE_COL.lock().unwrap());
+    let (line, col) = get_loc();
+
+    eprintln!("rick: {}: {}:{} error: {}", source_name(), line, col, reason);
+
+    std::process::exit(1);
+}
+
+pub fn report_err_at(loc: (u32, u32), reason: RickError) -> ! {
+    set_loc(loc.0, loc.1);
+
+    report_err(reason);
+}
+
+// -W name turns a warning on, -A name allows it. 'all' covers every warning.
+// Returns false for names that aren't warnings.
+pub fn set_warning(name: &str, enabled: bool) -> bool {
+    let names: Vec<&'static str> = if name == "all" {
+        WARNING_NAMES.to_vec()
+    } else {
+        match WARNING_NAMES.iter().find(|w| **w == name) {
+            Some(w) => vec![*w],
+            None => return false,
         }
+    };
+
+    let mut allowed = ALLOWED_WARNINGS.lock().unwrap();
+    allowed.retain(|w| !names.contains(w));
 
-        _ => {
-            eprintln!("Unreachable: Error message not yet implemented.");
-        },
+    if !enabled {
+        allowed.extend(names);
     }
 
+    true
+}
+
+// Warnings don't stop the program from running.
+pub fn report_warn_at(loc: (u32, u32), reason: RickWarning) {
+    if ALLOWED_WARNINGS.lock().unwrap().contains(&reason.name()) {
+        return;
+    }
+
+    eprintln!("rick: {}: {}:{} warning: {} [{}]", source_name(), loc.0, loc.1, reason, reason.name());
+}
+
+// Same as report_err_at, followed by a note pointing at related code.
+pub fn report_err_with_note(loc: (u32, u32), reason: RickError, note_loc: (u32, u32), note: String) -> ! {
+    eprintln!("rick: {}: {}:{} error: {}", source_name(), loc.0, loc.1, reason);
+    eprintln!("rick: {}: {}:{} note: {}", source_name(), note_loc.0, note_loc.1, note);
+
     std::process::exit(1);
-}
\ No newline at end of file
+}
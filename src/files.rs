@@ -0,0 +1,83 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+// Files opened by the program. A file value is an index into 'handles',
+// closing a file empties its slot so the index can't be used again.
+#[derive(Debug, Default)]
+pub struct Files {
+    handles: Vec<Option<Handle>>,
+}
+
+#[derive(Debug)]
+enum Handle {
+    Reader(BufReader<File>),
+
+    // unbuffered, so nothing is lost when the program exits without closing it
+    Writer(File),
+}
+
+impl Files {
+    pub fn new() -> Files {
+        Files::default()
+    }
+
+    // mode is "r" to read, "w" to truncate and write or "a" to append.
+    pub fn open(&mut self, path: &str, mode: &str) -> Result<usize, String> {
+        let handle: Handle = match mode {
+            "r" => File::open(path).map(|f| Handle::Reader(BufReader::new(f))),
+            "w" => File::create(path).map(Handle::Writer),
+            "a" => OpenOptions::new().append(true).create(true).open(path).map(Handle::Writer),
+            _ => return Err(format!("unknown file mode '{}', expected \"r\", \"w\" or \"a\"", mode)),
+        }.map_err(|e| format!("cannot open '{}': {}", path, e))?;
+
+        self.handles.push(Some(handle));
+
+        Ok(self.handles.len() - 1)
+    }
+
+    // The next line without its line break, None at the end of the file.
+    pub fn read_line(&mut self, id: usize) -> Result<Option<String>, String> {
+        let mut line: String = String::new();
+
+        match self.handles.get_mut(id) {
+            Some(Some(Handle::Reader(reader))) => {
+                if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                    return Ok(None);
+                }
+            },
+            Some(Some(Handle::Writer(_))) => return Err("file is not open for reading".into()),
+            _ => return Err("file is closed".into()),
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+
+    // Returns the number of bytes written.
+    pub fn write(&mut self, id: usize, text: &str) -> Result<usize, String> {
+        match self.handles.get_mut(id) {
+            Some(Some(Handle::Writer(file))) => {
+                file.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+
+                Ok(text.len())
+            },
+            Some(Some(Handle::Reader(_))) => Err("file is not open for writing".into()),
+            _ => Err("file is closed".into()),
+        }
+    }
+
+    // False if the file was already closed.
+    pub fn close(&mut self, id: usize) -> bool {
+        match self.handles.get_mut(id) {
+            Some(slot) => slot.take().is_some(),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use crate::ast::*;
+use crate::{error, error::*};
+use crate::optimise::constant_condition;
+use crate::properties::*;
+
+// Checks run on the finished AST, once the parser is done type checking.
+pub fn check_program(root: &ASTNode) {
+    if let ASTNode::Toplevel { funcdefs, tests } = root {
+        for fdef in funcdefs.iter().chain(tests) {
+            if let ASTNode::Funcdef { name, params, ret_type, variadic: _, source, loc, body } = &**fdef {
+                error::set_source(*source);
+
+                let mut flow: Flow = Flow::new(params);
+                flow.statement(body);
+
+                let mut unreachable: Vec<(u32, u32)> = vec![];
+                let returns: bool = always_returns(body, &mut unreachable);
+
+                for loc in unreachable {
+                    report_warn_at(loc, RickWarning::UnreachableCode);
+                }
+
+                if !returns && *ret_type & !FUNC != NONE {
+                    report_err_at(*loc, RickError::MissingReturn(name.clone()));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Local {
+    name: String,
+    loc: (u32, u32),
+    assigned: bool,
+}
+
+// Definite assignment: a local can only be read once every path leading
+// there has assigned it. 'vars' mirrors the walker's frame, declarations
+// push onto it and blocks truncate it again, so offsets index it directly.
+#[derive(Debug, Clone)]
+struct Flow {
+    vars: Vec<Local>,
+
+    // the path ended in a return, nothing after it runs
+    returned: bool,
+}
+
+impl Flow {
+    fn new(params: &[(String, u32, (u32, u32))]) -> Flow {
+        Flow {
+            vars: params.iter().map(|(name, _, loc)| Local { name: name.clone(), loc: *loc, assigned: true }).collect(),
+            returned: false,
+        }
+    }
+
+    fn statement(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Block { statements, locs: _ } => {
+                let size: usize = self.vars.len();
+
+                for statement in statements {
+                    self.statement(statement);
+                }
+
+                self.vars.truncate(size);
+            },
+            ASTNode::VarDef { vars } => {
+                // an optional declared without a value starts out as none
+                for (name, tipe, init, loc) in vars {
+                    if let Some(init) = init {
+                        self.expr(init);
+                    }
+
+                    let assigned: bool = init.is_some() || *tipe & (OPTIONAL | RESULT) == OPTIONAL;
+                    self.vars.push(Local { name: name.clone(), loc: *loc, assigned });
+                }
+            },
+            ASTNode::Let { offset, index, fill: _, rhs, loc } => {
+                self.expr(rhs);
+
+                // storing into an element needs the array itself
+                if let Some(index) = index {
+                    self.expr(index);
+                    self.require(*offset, *loc);
+                } else {
+                    self.assign(*offset);
+                }
+            },
+            ASTNode::Destructure { offsets, rhs, loc: _ } => {
+                self.expr(rhs);
+
+                for offset in offsets.iter().flatten() {
+                    self.assign(*offset);
+                }
+            },
+            ASTNode::Read { targets, propagate: _ } => {
+                for (offset, tipe, index, loc) in targets {
+                    if let Some(index) = index {
+                        self.expr(index);
+                        self.require(*offset, *loc);
+                    } else if *tipe & ARRAY != 0 {
+                        // filling a whole array needs the array to be there already
+                        self.require(*offset, *loc);
+                    } else {
+                        self.assign(*offset);
+                    }
+                }
+            },
+            ASTNode::If { branches, else_case } => {
+                let start: Flow = self.clone();
+                let mut merged: Flow = Flow { vars: vec![], returned: true };
+
+                for (cond, body) in branches {
+                    start.expr(cond);
+
+                    let mut branch: Flow = start.clone();
+                    branch.statement(body);
+                    merged.merge(branch);
+                }
+
+                match else_case {
+                    Some(else_case) => {
+                        let mut branch: Flow = start.clone();
+                        branch.statement(else_case);
+                        merged.merge(branch);
+                    },
+                    None => merged.merge(start),
+                }
+
+                *self = merged;
+            },
+            ASTNode::IfLet { offset, value, then_case, else_case } => {
+                self.expr(value);
+
+                let start: Flow = self.clone();
+                let mut merged: Flow = Flow { vars: vec![], returned: true };
+
+                let mut branch: Flow = start.clone();
+                branch.vars.push(Local { name: String::new(), loc: (0, 0), assigned: true });
+                branch.statement(then_case);
+                branch.vars.truncate(*offset as usize);
+                merged.merge(branch);
+
+                match else_case {
+                    Some(else_case) => {
+                        let mut branch: Flow = start.clone();
+                        branch.statement(else_case);
+                        merged.merge(branch);
+                    },
+                    None => merged.merge(start),
+                }
+
+                *self = merged;
+            },
+            ASTNode::While { condition, statement } => {
+                self.expr(condition);
+
+                // the body might not run at all, so nothing it assigns counts afterwards
+                let mut body: Flow = self.clone();
+                body.statement(statement);
+            },
+            ASTNode::Return { expr } => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+
+                self.returned = true;
+            },
+            _ => {
+                self.expr(node);
+            },
+        }
+    }
+
+    fn expr(&self, node: &ASTNode) {
+        match node {
+            ASTNode::GetVar { name: _, offset, loc } => {
+                self.require(*offset, *loc);
+            },
+            ASTNode::GetIndex { offset, idx, loc } => {
+                self.expr(idx);
+                self.require(*offset, *loc);
+            },
+            ASTNode::Call { name: _, args } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            },
+            ASTNode::Print { items, stderr: _ } | ASTNode::Tuple { items } => {
+                for item in items {
+                    self.expr(item);
+                }
+            },
+            ASTNode::BinaryOp { lhs, op: _, rhs } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            },
+            ASTNode::Conditional { condition, then_value, else_value } => {
+                self.expr(condition);
+                self.expr(then_value);
+                self.expr(else_value);
+            },
+            ASTNode::UnaryOp { op: _, value } | ASTNode::Some { value } | ASTNode::Ok { value }
+                | ASTNode::Discard { value } | ASTNode::Try { value, propagate: _ } => {
+                self.expr(value);
+            },
+            ASTNode::Err { message } => {
+                self.expr(message);
+            },
+            ASTNode::Assert { condition, message, source: _, loc: _ } => {
+                self.expr(condition);
+
+                if let Some(message) = message {
+                    self.expr(message);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn assign(&mut self, offset: u32) {
+        if let Some(var) = self.vars.get_mut(offset as usize) {
+            var.assigned = true;
+        }
+    }
+
+    // Code after a return never runs, so it can't read anything unassigned.
+    fn require(&self, offset: u32, loc: (u32, u32)) {
+        if self.returned {
+            return;
+        }
+
+        if let Some(var) = self.vars.get(offset as usize) {
+            if !var.assigned {
+                report_err_with_note(loc, RickError::UnassignedVariable(var.name.clone()), var.loc,
+                    format!("'{}' is declared here", var.name));
+            }
+        }
+    }
+
+    // Joins two paths, a variable counts as assigned only if both assigned it.
+    // A path that returned doesn't reach the join, so it doesn't get a say.
+    fn merge(&mut self, other: Flow) {
+        if other.returned {
+            return;
+        }
+        if self.returned {
+            *self = other;
+            return;
+        }
+
+        // a declaration that isn't wrapped in a block only exists on one side
+        for var in other.vars.iter().skip(self.vars.len()) {
+            self.vars.push(Local { assigned: false, ..var.clone() });
+        }
+        for (i, var) in self.vars.iter_mut().enumerate() {
+            var.assigned &= other.vars.get(i).is_some_and(|v| v.assigned);
+        }
+    }
+}
+
+// Whether every path through the statement ends in a return. There is no
+// 'break', so a loop whose condition is always true only ever leaves through
+// a return as well. Conditions are folded first, like the optimiser does.
+// Statements that follow one of these in a block are unreachable, their
+// locations are added to 'unreachable'.
+fn always_returns(node: &ASTNode, unreachable: &mut Vec<(u32, u32)>) -> bool {
+    match node {
+        ASTNode::Return { expr: _ } => true,
+        ASTNode::Block { statements, locs } => {
+            for (i, statement) in statements.iter().enumerate() {
+                if always_returns(statement, unreachable) {
+                    if let Some(loc) = locs.get(i + 1) {
+                        unreachable.push(*loc);
+                    }
+
+                    return true;
+                }
+            }
+
+            false
+        },
+        ASTNode::If { branches, else_case } => {
+            let mut returns: bool = true;
+
+            // once a branch is always taken, the ones after it never are
+            let mut taken: bool = false;
+
+            for (cond, body) in branches {
+                let body_returns: bool = always_returns(body, unreachable);
+
+                match (taken, constant_condition(cond)) {
+                    (true, _) | (false, Some(false)) => {},
+                    (false, Some(true)) => {
+                        returns &= body_returns;
+                        taken = true;
+                    },
+                    (false, None) => returns &= body_returns,
+                }
+            }
+
+            match else_case {
+                Some(else_case) => (always_returns(else_case, unreachable) || taken) && returns,
+                None => taken && returns,
+            }
+        },
+        ASTNode::IfLet { offset: _, value: _, then_case, else_case } => {
+            let returns: bool = always_returns(then_case, unreachable);
+
+            match else_case {
+                Some(else_case) => always_returns(else_case, unreachable) && returns,
+                None => false,
+            }
+        },
+        ASTNode::While { condition, statement } => {
+            always_returns(statement, unreachable);
+
+            constant_condition(condition) == Some(true)
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_text;
+
+    // Runs the statements of f() one by one and reports which of its locals
+    // are assigned at the end, the closing block would drop them otherwise.
+    fn assigned(body: &str) -> Vec<bool> {
+        let root: ASTNode = parse_text(&format!("func f(boolean c) {{\n{}\n}}\n", body));
+        let ASTNode::Toplevel { funcdefs, tests: _ } = root else { panic!("no toplevel") };
+        let ASTNode::Funcdef { params, body, .. } = &*funcdefs[0] else { panic!("no funcdef") };
+        let ASTNode::Block { statements, locs: _ } = &**body else { panic!("no body") };
+
+        let mut flow: Flow = Flow::new(params);
+        for statement in statements {
+            flow.statement(statement);
+        }
+
+        flow.vars.iter().skip(params.len()).map(|var| var.assigned).collect()
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(assigned("var integer x;"), [false]);
+        assert_eq!(assigned("var integer x = 1;"), [true]);
+        assert_eq!(assigned("var integer x;\nlet x = 2;"), [true]);
+        assert_eq!(assigned("var integer x, y = 3;"), [false, true]);
+    }
+
+    #[test]
+    fn optionals_start_as_none() {
+        assert_eq!(assigned("var optional integer x;"), [true]);
+        assert_eq!(assigned("var optional string array x;"), [true]);
+        assert_eq!(assigned("var result integer x;"), [false]);
+    }
+
+    #[test]
+    fn both_branches_must_assign() {
+        assert_eq!(assigned("var integer x;\nif c { let x = 1; }"), [false]);
+        assert_eq!(assigned("var integer x;\nif c { let x = 1; } else { let x = 2; }"), [true]);
+        assert_eq!(assigned("var integer x;\nif c { let x = 1; } elif !c { let x = 2; }"), [false]);
+        assert_eq!(assigned("var integer x;\nif c { let x = 1; } elif !c { let x = 2; } else { let x = 3; }"), [true]);
+    }
+
+    #[test]
+    fn returning_branches_do_not_count() {
+        assert_eq!(assigned("var integer x;\nif c { return; } else { let x = 2; }"), [true]);
+        assert_eq!(assigned("var integer x;\nif c { return; }\nlet x = 1;"), [true]);
+        assert_eq!(assigned("var integer x;\nif c { let x = 1; } else { return; }"), [true]);
+    }
+
+    #[test]
+    fn loops_might_not_run() {
+        assert_eq!(assigned("var integer x;\nwhile c { let x = 1; }"), [false]);
+    }
+
+    // Whether f() always returns, and the lines the statements it can't
+    // reach start on. The body starts on line 2.
+    fn returns(body: &str) -> (bool, Vec<u32>) {
+        let root: ASTNode = parse_text(&format!("func f(boolean c) integer {{\n{}\n}}\n", body));
+        let ASTNode::Toplevel { funcdefs, tests: _ } = root else { panic!("no toplevel") };
+        let ASTNode::Funcdef { body, .. } = &*funcdefs[0] else { panic!("no funcdef") };
+
+        let mut unreachable: Vec<(u32, u32)> = vec![];
+        let returns: bool = always_returns(body, &mut unreachable);
+
+        (returns, unreachable.iter().map(|loc| loc.0).collect())
+    }
+
+    #[test]
+    fn every_path_returns() {
+        assert_eq!(returns("return 1;"), (true, vec![]));
+        assert_eq!(returns("if c { return 1; }"), (false, vec![]));
+        assert_eq!(returns("if c { return 1; } else { return 2; }"), (true, vec![]));
+        assert_eq!(returns("if c { return 1; } elif !c { return 2; }"), (false, vec![]));
+        assert_eq!(returns("if let some(x) = some(1) { return x; } else { return 0; }"), (true, vec![]));
+    }
+
+    #[test]
+    fn constant_loop_conditions() {
+        assert_eq!(returns("while true { }"), (true, vec![]));
+        assert_eq!(returns("while 1 == 1 { return 1; }"), (true, vec![]));
+        assert_eq!(returns("while !(2 < 1) { return 1; }"), (true, vec![]));
+        assert_eq!(returns("while c { return 1; }"), (false, vec![]));
+        assert_eq!(returns("while 1 == 2 { return 1; }"), (false, vec![]));
+    }
+
+    #[test]
+    fn constant_branch_conditions() {
+        assert_eq!(returns("if 1 < 2 { return 1; }"), (true, vec![]));
+        assert_eq!(returns("if 2 < 1 { } else { return 1; }"), (true, vec![]));
+        assert_eq!(returns("if c { return 1; } elif true { return 2; }"), (true, vec![]));
+        assert_eq!(returns("if c { } elif true { return 2; }"), (false, vec![]));
+        assert_eq!(returns("if true { return 1; } elif c { } else { }"), (true, vec![]));
+    }
+
+    #[test]
+    fn unreachable_statements() {
+        assert_eq!(returns("return 1;\nprint(\"gone\");"), (true, vec![3]));
+        assert_eq!(returns("while 0 == 0 { }\nreturn 2;"), (true, vec![3]));
+        assert_eq!(returns("if c {\nreturn 1;\nprint(\"gone\");\n}\nreturn 2;"), (true, vec![4]));
+
+        // only the first statement that can't be reached is reported
+        assert_eq!(returns("return 1;\nreturn 2;\nreturn 3;"), (true, vec![3]));
+    }
+}
@@ -0,0 +1,157 @@
+use std::io;
+use std::io::BufRead;
+
+// Everything read from stdin that the program hasn't used yet. Lines are
+// pulled in as they are needed, so reading never waits for more input than
+// it has to.
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    pending: String,
+    pos: usize,
+    fixed: bool,
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input::default()
+    }
+
+    // Reads from the given text instead of stdin.
+    #[cfg(test)]
+    fn from_text(text: &str) -> Input {
+        Input { pending: text.to_string(), pos: 0, fixed: true }
+    }
+
+    // Next whitespace separated word, None once stdin has run out. When only
+    // whitespace is left on its line the rest of the line goes with it, so a
+    // readline() afterwards starts on the next one.
+    pub fn token(&mut self) -> Option<String> {
+        if !self.skip_whitespace() {
+            return None;
+        }
+
+        let rest: &str = &self.pending[self.pos..];
+        let len: usize = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token: String = rest[..len].to_string();
+        self.pos += len;
+
+        let rest: &str = &self.pending[self.pos..];
+        let line_len: usize = rest.find('\n').map_or(rest.len(), |i| i + 1);
+        if rest[..line_len].trim().is_empty() {
+            self.pos += line_len;
+        }
+
+        Some(token)
+    }
+
+    // The rest of the current line without its line break, None at the end of stdin.
+    pub fn line(&mut self) -> Option<String> {
+        if self.pos == self.pending.len() && !self.fill() {
+            return None;
+        }
+
+        let rest: &str = &self.pending[self.pos..];
+        let len: usize = rest.find('\n').unwrap_or(rest.len());
+        let line: String = rest[..len].trim_end_matches('\r').to_string();
+        self.pos += (len + 1).min(rest.len());
+
+        Some(line)
+    }
+
+    // True once nothing but whitespace is left. Only looks ahead, the
+    // whitespace is still there for readline().
+    pub fn at_end(&mut self) -> bool {
+        while self.pending[self.pos..].trim().is_empty() {
+            if !self.fill() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Moves up to the next word, false if there isn't one.
+    fn skip_whitespace(&mut self) -> bool {
+        loop {
+            let rest: &str = &self.pending[self.pos..];
+
+            match rest.find(|c: char| !c.is_whitespace()) {
+                Some(i) => {
+                    self.pos += i;
+                    return true;
+                },
+                None => {
+                    self.pos = self.pending.len();
+
+                    if !self.fill() {
+                        return false;
+                    }
+                },
+            }
+        }
+    }
+
+    // Appends another line from stdin, false at the end of it.
+    fn fill(&mut self) -> bool {
+        if self.pos == self.pending.len() {
+            self.pending.clear();
+            self.pos = 0;
+        }
+
+        if self.fixed {
+            return false;
+        }
+
+        match io::stdin().lock().read_line(&mut self.pending) {
+            Ok(n) => n > 0,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_split_on_any_whitespace() {
+        let mut input: Input = Input::from_text("  12 abc\t-3\n\n  x\r\n");
+
+        assert_eq!(input.token().as_deref(), Some("12"));
+        assert_eq!(input.token().as_deref(), Some("abc"));
+        assert_eq!(input.token().as_deref(), Some("-3"));
+        assert_eq!(input.token().as_deref(), Some("x"));
+        assert_eq!(input.token(), None);
+        assert!(input.at_end());
+    }
+
+    #[test]
+    fn token_takes_the_rest_of_a_blank_line() {
+        let mut input: Input = Input::from_text("24  \nhello world\n");
+
+        assert_eq!(input.token().as_deref(), Some("24"));
+        assert_eq!(input.line().as_deref(), Some("hello world"));
+        assert_eq!(input.line(), None);
+    }
+
+    #[test]
+    fn token_leaves_the_rest_of_a_line() {
+        let mut input: Input = Input::from_text("3 apples\r\nnext\n");
+
+        assert_eq!(input.token().as_deref(), Some("3"));
+        assert_eq!(input.line().as_deref(), Some(" apples"));
+        assert_eq!(input.line().as_deref(), Some("next"));
+    }
+
+    #[test]
+    fn last_line_without_a_break() {
+        let mut input: Input = Input::from_text("one\n\ntwo");
+
+        assert_eq!(input.line().as_deref(), Some("one"));
+        assert_eq!(input.line().as_deref(), Some(""));
+        assert!(!input.at_end());
+        assert_eq!(input.line().as_deref(), Some("two"));
+        assert_eq!(input.line(), None);
+        assert!(input.at_end());
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::properties::ARRAY;
+use crate::{error, error::*};
+
+// Warnings about code that does nothing useful: variables and parameters
+// that are never read, functions main never gets to and assignments whose
+// value is replaced before anything looks at it. Names starting with '_'
+// are left alone.
+pub fn check_program(root: &ASTNode) {
+    let mut calls: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut locs: Vec<(String, usize, (u32, u32))> = vec![];
+    let mut todo: Vec<String> = vec!["main".into()];
+
+    if let ASTNode::Toplevel { funcdefs, tests } = root {
+        for fdef in funcdefs {
+            if let ASTNode::Funcdef { name, params, ret_type: _, variadic: _, source, loc, body } = &**fdef {
+                error::set_source(*source);
+
+                let mut lint: Lint = Lint::new();
+                lint.function(name, params, body);
+
+                calls.insert(name.clone(), lint.calls);
+                locs.push((name.clone(), *source, *loc));
+            }
+        }
+
+        // functions only the tests use still count as used
+        for test in tests {
+            if let ASTNode::Funcdef { name, params, ret_type: _, variadic: _, source, loc: _, body } = &**test {
+                error::set_source(*source);
+
+                let mut lint: Lint = Lint::new();
+                lint.function(name, params, body);
+
+                todo.extend(lint.calls);
+            }
+        }
+    }
+
+    let mut reachable: HashSet<String> = HashSet::new();
+
+    while let Some(name) = todo.pop() {
+        if reachable.insert(name.clone()) {
+            if let Some(callees) = calls.get(&name) {
+                todo.extend(callees.iter().cloned());
+            }
+        }
+    }
+
+    for (name, source, loc) in locs {
+        if !reachable.contains(&name) && !is_silenced(&name) {
+            error::set_source(source);
+            report_warn_at(loc, RickWarning::UnusedFunction(name));
+        }
+    }
+}
+
+// util::_helper is silenced just like _helper
+fn is_silenced(name: &str) -> bool {
+    name.rsplit("::").next().unwrap_or(name).starts_with('_')
+}
+
+struct Decl {
+    name: String,
+    loc: (u32, u32),
+    param: bool,
+    read: bool,
+}
+
+struct Store {
+    decl: usize,
+    loc: (u32, u32),
+    read: bool,
+    overwritten: bool,
+}
+
+// What one path through the function knows. Slots line up with the
+// walker's frame, each one remembers the stores that may still be read.
+#[derive(Debug, Clone)]
+struct Path {
+    slots: Vec<Slot>,
+    returned: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    decl: usize,
+    pending: Vec<usize>,
+}
+
+struct Lint {
+    decls: Vec<Decl>,
+    stores: Vec<Store>,
+
+    // functions called from this one, builtins included
+    calls: HashSet<String>,
+}
+
+impl Lint {
+    fn new() -> Lint {
+        Lint {
+            decls: vec![],
+            stores: vec![],
+            calls: HashSet::new(),
+        }
+    }
+
+    fn function(&mut self, name: &str, params: &[(String, u32, (u32, u32))], body: &ASTNode) {
+        self.walk(params, body);
+
+        for (loc, warning) in self.warnings(name) {
+            report_warn_at(loc, warning);
+        }
+    }
+
+    fn walk(&mut self, params: &[(String, u32, (u32, u32))], body: &ASTNode) {
+        let mut path: Path = Path { slots: vec![], returned: false };
+
+        for (param, _, loc) in params {
+            let decl: usize = self.declare(param, *loc, true);
+            path.slots.push(Slot { decl, pending: vec![] });
+        }
+
+        self.statement(&mut path, body);
+    }
+
+    // In source order, for the function called 'name'.
+    fn warnings(&self, name: &str) -> Vec<((u32, u32), RickWarning)> {
+        let mut warnings: Vec<((u32, u32), RickWarning)> = vec![];
+
+        for decl in &self.decls {
+            if decl.read || decl.name.is_empty() || decl.name.starts_with('_') {
+                continue;
+            }
+
+            if decl.param {
+                warnings.push((decl.loc, RickWarning::UnusedParameter(name.to_string(), decl.name.clone())));
+            } else {
+                warnings.push((decl.loc, RickWarning::UnusedVariable(decl.name.clone())));
+            }
+        }
+
+        // unread variables already got a warning of their own
+        for store in &self.stores {
+            let decl: &Decl = &self.decls[store.decl];
+
+            if store.overwritten && !store.read && decl.read && !decl.name.starts_with('_') {
+                warnings.push((store.loc, RickWarning::UnusedAssignment(decl.name.clone())));
+            }
+        }
+
+        warnings.sort_by_key(|(loc, _)| *loc);
+
+        warnings
+    }
+
+    fn declare(&mut self, name: &str, loc: (u32, u32), param: bool) -> usize {
+        self.decls.push(Decl { name: name.to_string(), loc, param, read: false });
+
+        self.decls.len() - 1
+    }
+
+    fn statement(&mut self, path: &mut Path, node: &ASTNode) {
+        match node {
+            ASTNode::Block { statements, locs: _ } => {
+                let size: usize = path.slots.len();
+
+                for statement in statements {
+                    self.statement(path, statement);
+                }
+
+                path.slots.truncate(size);
+            },
+            ASTNode::VarDef { vars } => {
+                for (name, _, init, loc) in vars {
+                    let decl: usize = self.declare(name, *loc, false);
+                    let mut pending: Vec<usize> = vec![];
+
+                    if let Some(init) = init {
+                        self.expr(path, init);
+                        pending.push(self.store(decl, *loc));
+                    }
+
+                    path.slots.push(Slot { decl, pending });
+                }
+            },
+            ASTNode::Let { offset, index, fill: _, rhs, loc } => {
+                self.expr(path, rhs);
+
+                // storing into an element keeps the rest of the array alive
+                if let Some(index) = index {
+                    self.expr(path, index);
+                    self.read(path, *offset);
+                } else {
+                    self.write(path, *offset, *loc);
+                }
+            },
+            ASTNode::Destructure { offsets, rhs, loc } => {
+                self.expr(path, rhs);
+
+                for offset in offsets.iter().flatten() {
+                    self.write(path, *offset, *loc);
+                }
+            },
+            ASTNode::Read { targets, propagate: _ } => {
+                for (offset, tipe, index, loc) in targets {
+                    if let Some(index) = index {
+                        self.expr(path, index);
+                        self.read(path, *offset);
+                    } else if *tipe & ARRAY != 0 {
+                        self.read(path, *offset);
+                    } else {
+                        self.write(path, *offset, *loc);
+                    }
+                }
+            },
+            ASTNode::If { branches, else_case } => {
+                let start: Path = path.clone();
+                let mut merged: Path = Path { slots: vec![], returned: true };
+
+                for (cond, body) in branches {
+                    self.expr(&start, cond);
+
+                    let mut branch: Path = start.clone();
+                    self.statement(&mut branch, body);
+                    merged.merge(branch);
+                }
+
+                match else_case {
+                    Some(else_case) => {
+                        let mut branch: Path = start.clone();
+                        self.statement(&mut branch, else_case);
+                        merged.merge(branch);
+                    },
+                    None => merged.merge(start),
+                }
+
+                *path = merged;
+            },
+            ASTNode::IfLet { offset, value, then_case, else_case } => {
+                self.expr(path, value);
+
+                let start: Path = path.clone();
+                let mut merged: Path = Path { slots: vec![], returned: true };
+
+                // the binding has no name in the tree, it never gets a warning
+                let decl: usize = self.declare("", (0, 0), false);
+                let mut branch: Path = start.clone();
+                branch.slots.push(Slot { decl, pending: vec![] });
+                self.statement(&mut branch, then_case);
+                branch.slots.truncate(*offset as usize);
+                merged.merge(branch);
+
+                match else_case {
+                    Some(else_case) => {
+                        let mut branch: Path = start.clone();
+                        self.statement(&mut branch, else_case);
+                        merged.merge(branch);
+                    },
+                    None => merged.merge(start),
+                }
+
+                *path = merged;
+            },
+            ASTNode::While { condition, statement } => {
+                // Twice around the loop is enough for stores at the end of
+                // the body to meet the reads at the start of it.
+                for _ in 0..2 {
+                    self.expr(path, condition);
+
+                    let mut body: Path = path.clone();
+                    self.statement(&mut body, statement);
+                    path.merge(body);
+                }
+            },
+            ASTNode::Return { expr } => {
+                if let Some(expr) = expr {
+                    self.expr(path, expr);
+                }
+
+                path.returned = true;
+            },
+            _ => {
+                self.expr(path, node);
+            },
+        }
+    }
+
+    fn expr(&mut self, path: &Path, node: &ASTNode) {
+        match node {
+            ASTNode::GetVar { name: _, offset, loc: _ } => {
+                self.read(path, *offset);
+            },
+            ASTNode::GetIndex { offset, idx, loc: _ } => {
+                self.expr(path, idx);
+                self.read(path, *offset);
+            },
+            ASTNode::Call { name, args } => {
+                self.calls.insert(name.clone());
+
+                for arg in args {
+                    self.expr(path, arg);
+                }
+            },
+            ASTNode::Print { items, stderr: _ } | ASTNode::Tuple { items } => {
+                for item in items {
+                    self.expr(path, item);
+                }
+            },
+            ASTNode::BinaryOp { lhs, op: _, rhs } => {
+                self.expr(path, lhs);
+                self.expr(path, rhs);
+            },
+            ASTNode::Conditional { condition, then_value, else_value } => {
+                self.expr(path, condition);
+                self.expr(path, then_value);
+                self.expr(path, else_value);
+            },
+            ASTNode::UnaryOp { op: _, value } | ASTNode::Some { value } | ASTNode::Ok { value }
+                | ASTNode::Discard { value } | ASTNode::Try { value, propagate: _ } => {
+                self.expr(path, value);
+            },
+            ASTNode::Err { message } => {
+                self.expr(path, message);
+            },
+            ASTNode::Assert { condition, message, source: _, loc: _ } => {
+                self.expr(path, condition);
+
+                if let Some(message) = message {
+                    self.expr(path, message);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // The same assignment is seen again on the second trip around a loop.
+    fn store(&mut self, decl: usize, loc: (u32, u32)) -> usize {
+        if let Some(store) = self.stores.iter().position(|s| s.decl == decl && s.loc == loc) {
+            return store;
+        }
+
+        self.stores.push(Store { decl, loc, read: false, overwritten: false });
+
+        self.stores.len() - 1
+    }
+
+    fn read(&mut self, path: &Path, offset: u32) {
+        if let Some(slot) = path.slots.get(offset as usize) {
+            self.decls[slot.decl].read = true;
+
+            for store in &slot.pending {
+                self.stores[*store].read = true;
+            }
+        }
+    }
+
+    fn write(&mut self, path: &mut Path, offset: u32, loc: (u32, u32)) {
+        if path.returned {
+            return;
+        }
+
+        if let Some(decl) = path.slots.get(offset as usize).map(|slot| slot.decl) {
+            let store: usize = self.store(decl, loc);
+            let slot: &mut Slot = &mut path.slots[offset as usize];
+
+            for pending in &slot.pending {
+                self.stores[*pending].overwritten = true;
+            }
+            slot.pending = vec![store];
+        }
+    }
+}
+
+impl Path {
+    // Stores from either side may still be read after the join. A path that
+    // returned never gets there.
+    fn merge(&mut self, other: Path) {
+        if other.returned {
+            return;
+        }
+        if self.returned {
+            *self = other;
+            return;
+        }
+
+        for (i, slot) in other.slots.into_iter().enumerate() {
+            match self.slots.get_mut(i) {
+                Some(mine) if mine.decl == slot.decl => {
+                    for store in slot.pending {
+                        if !mine.pending.contains(&store) {
+                            mine.pending.push(store);
+                        }
+                    }
+                },
+                Some(_) => {},
+                None => self.slots.push(slot),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_text;
+
+    // Warnings for f() as (line, name), f's body starts on line 2.
+    fn warnings(body: &str) -> Vec<(u32, &'static str)> {
+        let root: ASTNode = parse_text(&format!("func f(boolean _c) integer {{\n{}\n}}\n", body));
+        let ASTNode::Toplevel { funcdefs, tests: _ } = root else { panic!("no toplevel") };
+        let ASTNode::Funcdef { name, params, body, .. } = &*funcdefs[0] else { panic!("no funcdef") };
+
+        let mut lint: Lint = Lint::new();
+        lint.walk(params, body);
+
+        lint.warnings(name).iter().map(|(loc, warning)| (loc.0, warning.name())).collect()
+    }
+
+    #[test]
+    fn overwritten_store() {
+        assert_eq!(warnings("var integer x = 1;\nlet x = 2;\nreturn x;"), [(2, "unused-assignment")]);
+        assert_eq!(warnings("var integer x = 1;\nlet x = x + 1;\nreturn x;"), []);
+    }
+
+    #[test]
+    fn unread_variable_is_not_also_an_unused_store() {
+        assert_eq!(warnings("var integer x = 1;\nlet x = 2;\nreturn 0;"), [(2, "unused-variable")]);
+    }
+
+    #[test]
+    fn store_read_on_one_path() {
+        assert_eq!(warnings("var integer x = 1;\nif _c { return x; }\nlet x = 2;\nreturn x;"), []);
+        assert_eq!(warnings("var integer x = 1;\nif _c { let x = 3; }\nlet x = 2;\nreturn x;"),
+            [(2, "unused-assignment"), (3, "unused-assignment")]);
+    }
+
+    #[test]
+    fn store_read_next_time_around_a_loop() {
+        let body: &str = "var integer x = 0;\nvar integer y = 0;\nwhile _c {\nlet y = y + x;\nlet x = 1;\n}\nreturn y;";
+        assert_eq!(warnings(body), []);
+    }
+
+    #[test]
+    fn store_after_return_is_ignored() {
+        assert_eq!(warnings("var integer x = 1;\nreturn x;\nlet x = 2;"), []);
+    }
+
+    #[test]
+    fn element_store_keeps_the_array() {
+        assert_eq!(warnings("var integer array a;\nlet a = array 3;\nlet a[0] = 1;\nreturn a[0];"), []);
+    }
+
+    #[test]
+    fn unused_parameters_and_silenced_names() {
+        let root: ASTNode = parse_text("func g(integer a, integer _b) {\n}\n");
+        let ASTNode::Toplevel { funcdefs, tests: _ } = root else { panic!("no toplevel") };
+        let ASTNode::Funcdef { name, params, body, .. } = &*funcdefs[0] else { panic!("no funcdef") };
+
+        let mut lint: Lint = Lint::new();
+        lint.walk(params, body);
+
+        // reported at the parameter itself, not at the function
+        let found: Vec<((u32, u32), &str)> = lint.warnings(name).iter().map(|(loc, warning)| (*loc, warning.name())).collect();
+        assert_eq!(found, [((1, 16), "unused-parameter")]);
+        assert!(is_silenced("util::_helper"));
+        assert!(!is_silenced("util::helper"));
+    }
+}
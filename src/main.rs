@@ -4,7 +4,6 @@ mod token;
 // use token::*;
 
 mod scanner;
-use scanner::*;
 
 mod parser;
 use parser::*;
@@ -14,37 +13,200 @@ use error::*;
 
 mod properties;
 
+mod module;
+use module::*;
+
 mod ast;
 use ast::*;
 
 mod value;
 
+mod builtins;
+
+mod flow;
+
+mod lint;
+
+mod optimise;
+
+mod input;
+
+mod files;
+
+mod random;
+
 mod walker;
 use walker::*;
 
+const USAGE: &str = "USAGE: rick [run | test | check | tokens | ast] [options] [--] <filename> [--] [args...]";
+
+const HELP: &str = "
+Commands:
+    run       run the program, the default when no command is given
+    test      run the test blocks instead of main
+    check     scan, parse and type check without running anything
+    tokens    print the tokens of every loaded file
+    ast       print the syntax tree after type checking
+
+Options:
+    -W <warning>, -A <warning>    turn a warning on or off, 'all' covers every warning
+    --no-inline                   don't inline small functions
+    --seed <n>                    seed the random number generator
+    --help                        print this message
+    --version                     print the version
+
+Everything after the file is passed to the program, a '--' right after it is
+dropped so the program can take arguments that look like options.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Run,
+    Test,
+    Check,
+    Tokens,
+    Ast,
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let mut i: usize = 1;
+    let mut inline: bool = true;
+    let mut seed: Option<u64> = None;
+
+    // only a command if a file follows, so a program can still be called 'test'
+    let named: Option<Command> = match args.get(1).map(|a| a.as_str()) {
+        Some("run") => Some(Command::Run),
+        Some("test") => Some(Command::Test),
+        Some("check") => Some(Command::Check),
+        Some("tokens") => Some(Command::Tokens),
+        Some("ast") => Some(Command::Ast),
+        _ => None,
+    };
+
+    let mut command: Command = Command::Run;
+    if let Some(named) = named.filter(|_| args.len() > 2) {
+        command = named;
+        i += 1;
+    }
 
-    // TODO: add more options
-    if args.len() != 2 {
-        eprintln!("USAGE: rick <filename>");
+    // -W name / -A name, or glued together as -Wname, --no-inline and --seed n.
+    // '--' ends the options, for files whose name starts with '-'.
+    while i < args.len() && args[i].starts_with('-') {
+        if args[i] == "--" {
+            i += 1;
+            break;
+        }
+
+        if args[i] == "--help" {
+            println!("{}\n{}", USAGE, HELP);
+            std::process::exit(0);
+        }
+
+        if args[i] == "--version" {
+            println!("rick {}", env!("CARGO_PKG_VERSION"));
+            std::process::exit(0);
+        }
+
+        if args[i] == "--no-inline" {
+            inline = false;
+            i += 1;
+            continue;
+        }
+
+        if args[i] == "--seed" {
+            let value: String = args.get(i + 1).cloned().unwrap_or_default();
+
+            match value.parse() {
+                Ok(n) => seed = Some(n),
+                Err(_) => {
+                    eprintln!("rick: --seed expects a non-negative integer, found '{}'", value);
+                    std::process::exit(1);
+                },
+            }
+
+            i += 2;
+            continue;
+        }
+
+        if !args[i].starts_with("-W") && !args[i].starts_with("-A") {
+            eprintln!("rick: unknown option '{}'\n{}", args[i], USAGE);
+            std::process::exit(1);
+        }
+
+        let enabled: bool = args[i].starts_with("-W");
+        let mut name: String = args[i][2..].to_string();
+
+        if name.is_empty() {
+            i += 1;
+            name = args.get(i).cloned().unwrap_or_default();
+        }
+
+        if !error::set_warning(&name, enabled) {
+            eprintln!("rick: unknown warning '{}', expected one of: all, {}", name, WARNING_NAMES.join(", "));
+            std::process::exit(1);
+        }
+
+        i += 1;
+    }
+
+    if i >= args.len() {
+        eprintln!("{}\nTry 'rick --help' for more information.", USAGE);
         std::process::exit(1);
     }
 
-    error::set_source(args[1].clone());
-    error::set_loc(1, 0);
+    // the program sees its own file followed by whatever comes after it
+    args.drain(0..i);
+    if args.get(1).is_some_and(|a| a == "--") {
+        args.remove(1);
+    }
 
-    let mut scanner: Scanner = Scanner::new(args[1].clone());
-    
-    let tokens = scanner.scan_source();
+    let mut loader: Loader = Loader::new();
 
-    let mut parser: Parser = Parser::new(tokens);
+    let modules: Vec<Module> = loader.load_program(args[0].clone());
 
-    let root_node: ASTNode = parser.parse_tok_stream();
+    if command == Command::Tokens {
+        print_tokens(&modules);
+        return;
+    }
+
+    let mut parser: Parser = Parser::new(modules);
+
+    let mut root_node: ASTNode = parser.parse_tok_stream(command != Command::Test);
+
+    flow::check_program(&root_node);
+    lint::check_program(&root_node);
+
+    match command {
+        Command::Check => return,
+        Command::Ast => {
+            print!("{}", root_node);
+            return;
+        },
+        _ => {},
+    }
+
+    optimise::optimise(&mut root_node, inline);
 
     let mut walker: Walker = Walker::new(args, root_node);
 
-    let exit_code = walker.walk();
+    if let Some(seed) = seed {
+        walker.seed(seed);
+    }
+
+    let exit_code = if command == Command::Test { walker.run_tests() } else { walker.walk() };
 
     std::process::exit(exit_code);
 }
+
+// One token per line with its position, imported files get a heading of their own.
+fn print_tokens(modules: &[Module]) {
+    for module in modules {
+        if !module.name.is_empty() {
+            println!("\n{}:", error::source_name_of(module.source));
+        }
+
+        for (token, (line, col)) in &module.tokens {
+            println!("{}:{}\t{:?}", line, col, token);
+        }
+    }
+}
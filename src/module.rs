@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::{error, error::*};
+use crate::scanner::Scanner;
+use crate::token::*;
+
+// A scanned source file. The file handed to rick is the root module and has
+// an empty name, every imported file is namespaced by its file stem,
+// e.g. functions in 'util.ric' are called as 'util::print_grid(...)'.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub source: usize,
+    pub tokens: Vec<(Token, (u32, u32))>,
+
+    // names of the modules this one imports
+    pub imports: Vec<String>,
+}
+
+pub struct Loader {
+    modules: Vec<Module>,
+
+    // canonical path -> index into modules, so every file is scanned once
+    loaded: HashMap<PathBuf, usize>,
+
+    // files that are still busy resolving their own imports
+    stack: Vec<PathBuf>,
+
+    search_path: Vec<PathBuf>,
+}
+
+impl Loader {
+    // Imports are looked up relative to the importing file first,
+    // then in each directory listed in RICK_PATH.
+    pub fn new() -> Loader {
+        let mut search_path: Vec<PathBuf> = vec![];
+
+        if let Some(paths) = env::var_os("RICK_PATH") {
+            search_path = env::split_paths(&paths).collect();
+        }
+
+        Loader {
+            modules: vec![],
+            loaded: HashMap::new(),
+            stack: vec![],
+            search_path,
+        }
+    }
+
+    pub fn load_program(&mut self, filename: String) -> Vec<Module> {
+        let path: PathBuf = PathBuf::from(filename);
+
+        if !path.is_file() {
+            eprintln!("rick: {}: no such file", path.display());
+            std::process::exit(1);
+        }
+
+        self.load(path, String::new());
+
+        std::mem::take(&mut self.modules)
+    }
+
+    fn load(&mut self, path: PathBuf, name: String) -> usize {
+        let canonical: PathBuf = path.canonicalize().unwrap_or(path.clone());
+        let source: usize = error::add_source(path.display().to_string());
+
+        let mut scanner: Scanner = Scanner::new(path.display().to_string());
+        let tokens = scanner.scan_source();
+
+        let idx: usize = self.modules.len();
+        self.modules.push(Module { name, source, tokens: tokens.clone(), imports: vec![] });
+        self.loaded.insert(canonical.clone(), idx);
+        self.stack.push(canonical);
+
+        let mut i: usize = 0;
+        while i < tokens.len() {
+            if tokens[i].0 != Token::Import {
+                i += 1;
+                continue;
+            }
+
+            error::set_source(source);
+
+            // import util;  or  import "lib/util.ric";
+            let spec: String = match &tokens[i + 1].0 {
+                Token::Identifier(id) => format!("{}.ric", id),
+                Token::StringLiteral(s) => s.clone(),
+                other => report_err_at(tokens[i + 1].1, RickError::Expected(other.clone(), Token::Identifier("module".into()))),
+            };
+
+            if tokens[i + 2].0 != Token::Semicolon {
+                report_err_at(tokens[i + 2].1, RickError::Expected(tokens[i + 2].0.clone(), Token::Semicolon));
+            }
+
+            let file: PathBuf = match self.resolve(&path, &spec) {
+                Some(file) => file,
+                None => report_err_at(tokens[i + 1].1, RickError::ModuleNotFound(spec)),
+            };
+            let file_canonical: PathBuf = file.canonicalize().unwrap_or(file.clone());
+
+            if let Some(start) = self.stack.iter().position(|p| *p == file_canonical) {
+                let mut chain: Vec<String> = self.stack[start..].iter().map(|p| p.display().to_string()).collect();
+                chain.push(file_canonical.display().to_string());
+
+                report_err_at(tokens[i + 1].1, RickError::ImportCycle(chain.join(" -> ")));
+            }
+
+            let dep: usize = match self.loaded.get(&file_canonical) {
+                Some(dep) => *dep,
+                None => {
+                    let mod_name: String = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+                    if self.modules.iter().any(|m| m.name == mod_name) {
+                        report_err_at(tokens[i + 1].1, RickError::ModuleNameClash(mod_name));
+                    }
+
+                    self.load(file, mod_name)
+                },
+            };
+
+            let dep_name: String = self.modules[dep].name.clone();
+            self.modules[idx].imports.push(dep_name);
+
+            i += 3;
+        }
+
+        self.stack.pop();
+
+        idx
+    }
+
+    fn resolve(&self, importer: &Path, spec: &str) -> Option<PathBuf> {
+        let dir: &Path = importer.parent().unwrap_or(Path::new("."));
+
+        std::iter::once(dir.to_path_buf())
+            .chain(self.search_path.iter().cloned())
+            .map(|d| d.join(spec))
+            .find(|p| p.is_file())
+    }
+}
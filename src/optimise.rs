@@ -0,0 +1,631 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::builtins;
+use crate::properties::NONE;
+use crate::token::Token;
+use crate::value::Value;
+
+// Functions whose returned expression has more nodes than this are left as calls.
+const INLINE_THRESHOLD: usize = 24;
+
+// Runs on the checked AST right before it is handed to the walker.
+pub fn optimise(root: &mut ASTNode, inline: bool) {
+    fold(root);
+
+    if inline {
+        inline_calls(root);
+    }
+}
+
+// Evaluates operators on literals ahead of time, drops identities like
+// 'x + 0' and removes branches that can never run. Anything that could fail
+// at runtime (division by zero, overflow) is left for the walker to report.
+fn fold(node: &mut ASTNode) {
+    match node {
+        ASTNode::Toplevel { funcdefs, tests } => {
+            for fdef in funcdefs.iter_mut().chain(tests) {
+                fold(fdef);
+            }
+        },
+        ASTNode::Funcdef { name: _, params: _, ret_type: _, variadic: _, source: _, loc: _, body } => {
+            fold(body);
+        },
+        ASTNode::Block { statements, locs: _ } => {
+            for statement in statements {
+                fold(statement);
+            }
+        },
+        ASTNode::VarDef { vars } => {
+            for (_, _, init, _) in vars {
+                if let Some(init) = init {
+                    fold(init);
+                }
+            }
+        },
+        ASTNode::Call { name: _, args } => {
+            for arg in args {
+                fold(arg);
+            }
+        },
+        ASTNode::Let { offset: _, index, fill: _, rhs, loc: _ } => {
+            if let Some(index) = index {
+                fold(index);
+            }
+            fold(rhs);
+        },
+        ASTNode::Read { targets, propagate: _ } => {
+            for (_, _, index, _) in targets {
+                if let Some(index) = index {
+                    fold(index);
+                }
+            }
+        },
+        ASTNode::GetIndex { offset: _, idx: index, loc: _ } => {
+            fold(index);
+        },
+        ASTNode::Print { items, stderr: _ } | ASTNode::Tuple { items } => {
+            for item in items {
+                fold(item);
+            }
+        },
+        ASTNode::Return { expr: Some(value) } | ASTNode::Destructure { offsets: _, rhs: value, loc: _ }
+            | ASTNode::Some { value } | ASTNode::Ok { value } | ASTNode::Err { message: value }
+            | ASTNode::Discard { value } | ASTNode::Try { value, propagate: _ } => {
+            fold(value);
+        },
+        ASTNode::Assert { condition, message, source: _, loc: _ } => {
+            fold(condition);
+
+            if let Some(message) = message {
+                fold(message);
+            }
+        },
+        ASTNode::IfLet { offset: _, value, then_case, else_case } => {
+            fold(value);
+            fold(then_case);
+
+            if let Some(else_case) = else_case {
+                fold(else_case);
+            }
+        },
+        ASTNode::If { branches, else_case } => {
+            fold_if(branches, else_case);
+
+            // every condition was false, or the first one is always true
+            if branches.is_empty() {
+                *node = match else_case.take() {
+                    Some(else_case) => *else_case,
+                    None => empty_block(),
+                };
+            }
+        },
+        ASTNode::While { condition, statement } => {
+            fold(condition);
+
+            if let ASTNode::Value { val: Value::Boolean(false) } = **condition {
+                *node = empty_block();
+            } else {
+                fold(statement);
+            }
+        },
+        ASTNode::Conditional { condition, then_value, else_value } => {
+            fold(condition);
+            fold(then_value);
+            fold(else_value);
+
+            if let ASTNode::Value { val: Value::Boolean(b) } = **condition {
+                let chosen: &mut Box<ASTNode> = if b { then_value } else { else_value };
+                *node = std::mem::replace(&mut **chosen, empty_block());
+            }
+        },
+        ASTNode::UnaryOp { op, value } => {
+            fold(value);
+
+            if let ASTNode::Value { val } = &**value {
+                if let Some(val) = fold_unary(op, val) {
+                    *node = ASTNode::Value { val };
+                }
+            }
+        },
+        ASTNode::BinaryOp { lhs, op, rhs } => {
+            fold(lhs);
+            fold(rhs);
+
+            if let (ASTNode::Value { val: l }, ASTNode::Value { val: r }) = (&**lhs, &**rhs) {
+                if let Some(val) = fold_binary(op, l, r) {
+                    *node = ASTNode::Value { val };
+                }
+            } else if let ASTNode::Value { val } = &**rhs {
+                if is_right_identity(op, val) {
+                    *node = std::mem::replace(&mut **lhs, empty_block());
+                }
+            } else if let ASTNode::Value { val } = &**lhs {
+                if is_left_identity(op, val) {
+                    *node = std::mem::replace(&mut **rhs, empty_block());
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+// What a condition folds down to, None if it isn't known before running.
+pub fn constant_condition(condition: &ASTNode) -> Option<bool> {
+    let mut condition: ASTNode = condition.clone();
+    fold(&mut condition);
+
+    match condition {
+        ASTNode::Value { val: Value::Boolean(b) } => Some(b),
+        _ => None,
+    }
+}
+
+// Drops branches whose condition is false. A branch that is always taken
+// becomes the else case and cuts off everything after it.
+fn fold_if(branches: &mut Vec<(Box<ASTNode>, Box<ASTNode>)>, else_case: &mut Option<Box<ASTNode>>) {
+    let mut kept: Vec<(Box<ASTNode>, Box<ASTNode>)> = vec![];
+
+    for (mut cond, mut body) in std::mem::take(branches) {
+        fold(&mut cond);
+        fold(&mut body);
+
+        match *cond {
+            ASTNode::Value { val: Value::Boolean(false) } => {},
+            ASTNode::Value { val: Value::Boolean(true) } => {
+                *else_case = Some(body);
+                *branches = kept;
+                return;
+            },
+            _ => kept.push((cond, body)),
+        }
+    }
+
+    if let Some(else_case) = else_case {
+        fold(else_case);
+    }
+    *branches = kept;
+}
+
+fn empty_block() -> ASTNode {
+    ASTNode::Block {
+        statements: vec![],
+        locs: vec![],
+    }
+}
+
+fn fold_unary(op: &Token, val: &Value) -> Option<Value> {
+    match (op, val) {
+        (Token::Sub, Value::Integer(i)) => i.checked_neg().map(Value::Integer),
+        (Token::Sub, Value::Float(f)) => Some(Value::Float(-f)),
+        (Token::Negate, Value::Boolean(_)) | (Token::BitNot, Value::Integer(_)) => Some(!val.clone()),
+        _ => None,
+    }
+}
+
+fn fold_binary(op: &Token, lhs: &Value, rhs: &Value) -> Option<Value> {
+    match (lhs, rhs) {
+        (Value::Integer(l), Value::Integer(r)) => {
+            let (l, r) = (*l, *r);
+
+            match op {
+                Token::Add => l.checked_add(r).map(Value::Integer),
+                Token::Sub => l.checked_sub(r).map(Value::Integer),
+                Token::Mul => l.checked_mul(r).map(Value::Integer),
+                Token::Div => l.checked_div(r).map(Value::Integer),
+                Token::Mod => l.checked_rem(r).map(Value::Integer),
+                Token::Pow if r >= 0 => l.checked_pow(r as u32).map(Value::Integer),
+                Token::BitAnd => Some(lhs.clone() & rhs.clone()),
+                Token::BitOr => Some(lhs.clone() | rhs.clone()),
+                Token::BitXor => Some(lhs.clone() ^ rhs.clone()),
+                Token::Shl => Some(lhs.clone() << rhs.clone()),
+                Token::Shr => Some(lhs.clone() >> rhs.clone()),
+                Token::Ushr => Some(lhs.ushr_value(rhs)),
+                _ => fold_comparison(op, lhs, rhs),
+            }
+        },
+        (Value::Float(l), Value::Float(r)) => {
+            match op {
+                Token::Add => Some(Value::Float(l + r)),
+                Token::Sub => Some(Value::Float(l - r)),
+                Token::Mul => Some(Value::Float(l * r)),
+                Token::Div => Some(Value::Float(l / r)),
+                Token::Mod => Some(Value::Float(l % r)),
+                _ => fold_comparison(op, lhs, rhs),
+            }
+        },
+        (Value::String(l), Value::String(r)) if *op == Token::Add => {
+            Some(Value::String(format!("{}{}", l, r)))
+        },
+        (Value::String(_), Value::String(_)) | (Value::Boolean(_), Value::Boolean(_))
+            | (Value::Char(_), Value::Char(_)) => {
+            fold_comparison(op, lhs, rhs)
+        },
+        _ => None,
+    }
+}
+
+fn fold_comparison(op: &Token, lhs: &Value, rhs: &Value) -> Option<Value> {
+    let ordered: bool = !matches!(lhs, Value::String(_) | Value::Boolean(_));
+
+    match op {
+        Token::Eq => Some(Value::Boolean(lhs.is_eq(rhs))),
+        Token::Neq => Some(Value::Boolean(lhs.is_neq(rhs))),
+        Token::Gt if ordered => Some(Value::Boolean(lhs.is_gt(rhs))),
+        Token::Gte if ordered => Some(Value::Boolean(lhs.is_gte(rhs))),
+        Token::Lt if ordered => Some(Value::Boolean(lhs.is_lt(rhs))),
+        Token::Lte if ordered => Some(Value::Boolean(lhs.is_lte(rhs))),
+        _ => None,
+    }
+}
+
+// x + 0, x - 0, x * 1, x / 1, x ** 1, x | 0, x ^ 0 and shifts by 0
+fn is_right_identity(op: &Token, val: &Value) -> bool {
+    match val {
+        Value::Integer(0) => matches!(op, Token::Add | Token::Sub | Token::BitOr | Token::BitXor
+                | Token::Shl | Token::Shr | Token::Ushr),
+        Value::Integer(1) => matches!(op, Token::Mul | Token::Div | Token::Pow),
+        Value::Float(f) if *f == 1.0 => matches!(op, Token::Mul | Token::Div),
+        _ => false,
+    }
+}
+
+// 0 + x, 1 * x, 0 | x and 0 ^ x
+fn is_left_identity(op: &Token, val: &Value) -> bool {
+    match val {
+        Value::Integer(0) => matches!(op, Token::Add | Token::BitOr | Token::BitXor),
+        Value::Integer(1) => matches!(op, Token::Mul),
+        Value::Float(f) if *f == 1.0 => matches!(op, Token::Mul),
+        _ => false,
+    }
+}
+
+// Pastes small leaf functions into their callers. A candidate is a function
+// whose whole body is 'return expr;', where expr calls nothing but builtins
+// and cannot bail out with '?'. Leaves can't be recursive, so nothing has to
+// be inlined more than one level deep.
+fn inline_calls(root: &mut ASTNode) {
+    let mut candidates: HashMap<String, (usize, ASTNode)> = HashMap::new();
+
+    if let ASTNode::Toplevel { funcdefs, tests } = root {
+        for fdef in funcdefs.iter() {
+            if let ASTNode::Funcdef { name, params, ret_type, variadic, source: _, loc: _, body } = &**fdef {
+                if *variadic || *ret_type == NONE || name == "main" {
+                    continue;
+                }
+
+                if let ASTNode::Block { statements, locs: _ } = &**body {
+                    if let [statement] = &statements[..] {
+                        if let ASTNode::Return { expr: Some(expr) } = &**statement {
+                            if leaf_size(expr).is_some_and(|size| size <= INLINE_THRESHOLD) {
+                                candidates.insert(name.clone(), (params.len(), *expr.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for fdef in funcdefs.iter_mut().chain(tests) {
+            if let ASTNode::Funcdef { name: _, params, ret_type: _, variadic: _, source: _, loc: _, body } = &mut **fdef {
+                inline_in(body, params.len() as u32, &candidates);
+            }
+        }
+    }
+}
+
+// Number of nodes in an expression that can be inlined, None if it can't.
+fn leaf_size(node: &ASTNode) -> Option<usize> {
+    let children: usize = match node {
+        ASTNode::Value { val: _ } | ASTNode::GetVar { name: _, offset: _, loc: _ } => 0,
+        ASTNode::GetIndex { offset: _, idx: value, loc: _ } | ASTNode::UnaryOp { op: _, value }
+            | ASTNode::Some { value } | ASTNode::Ok { value } | ASTNode::Err { message: value }
+            | ASTNode::Try { value, propagate: false } => {
+            leaf_size(value)?
+        },
+        ASTNode::BinaryOp { lhs, op: _, rhs } => leaf_size(lhs)? + leaf_size(rhs)?,
+        ASTNode::Conditional { condition, then_value, else_value } => {
+            leaf_size(condition)? + leaf_size(then_value)? + leaf_size(else_value)?
+        },
+        ASTNode::Call { name, args } if builtins::lookup(name).is_some() => {
+            args.iter().map(|arg| leaf_size(arg)).sum::<Option<usize>>()?
+        },
+        ASTNode::Tuple { items } => items.iter().map(|item| leaf_size(item)).sum::<Option<usize>>()?,
+        _ => return None,
+    };
+
+    Some(children + 1)
+}
+
+// Moves an inlined body's variables up to where its arguments will sit in
+// the caller's frame.
+fn shift_offsets(node: &mut ASTNode, base: u32) {
+    match node {
+        ASTNode::GetVar { name: _, offset, loc: _ } => {
+            *offset += base;
+        },
+        ASTNode::GetIndex { offset, idx, loc: _ } => {
+            *offset += base;
+            shift_offsets(idx, base);
+        },
+        ASTNode::UnaryOp { op: _, value } | ASTNode::Some { value } | ASTNode::Ok { value }
+            | ASTNode::Err { message: value } | ASTNode::Try { value, propagate: _ } => {
+            shift_offsets(value, base);
+        },
+        ASTNode::BinaryOp { lhs, op: _, rhs } => {
+            shift_offsets(lhs, base);
+            shift_offsets(rhs, base);
+        },
+        ASTNode::Conditional { condition, then_value, else_value } => {
+            shift_offsets(condition, base);
+            shift_offsets(then_value, base);
+            shift_offsets(else_value, base);
+        },
+        ASTNode::Call { name: _, args: items } | ASTNode::Tuple { items } => {
+            for item in items {
+                shift_offsets(item, base);
+            }
+        },
+        _ => {},
+    }
+}
+
+// depth is how many variables are in the caller's frame at this point, the
+// same count the parser hands out offsets from.
+fn inline_in(node: &mut ASTNode, depth: u32, candidates: &HashMap<String, (usize, ASTNode)>) {
+    match node {
+        ASTNode::Block { statements, locs: _ } => {
+            let mut depth: u32 = depth;
+
+            for statement in statements {
+                inline_in(statement, depth, candidates);
+
+                if let ASTNode::VarDef { vars } = &**statement {
+                    depth += vars.len() as u32;
+                }
+            }
+        },
+        ASTNode::VarDef { vars } => {
+            // every variable is pushed as soon as its initialiser is done
+            for (i, (_, _, init, _)) in vars.iter_mut().enumerate() {
+                if let Some(init) = init {
+                    inline_in(init, depth + i as u32, candidates);
+                }
+            }
+        },
+        ASTNode::If { branches, else_case } => {
+            for (cond, body) in branches {
+                inline_in(cond, depth, candidates);
+                inline_in(body, depth, candidates);
+            }
+
+            if let Some(else_case) = else_case {
+                inline_in(else_case, depth, candidates);
+            }
+        },
+        ASTNode::Assert { condition, message, source: _, loc: _ } => {
+            inline_in(condition, depth, candidates);
+
+            if let Some(message) = message {
+                inline_in(message, depth, candidates);
+            }
+        },
+        ASTNode::IfLet { offset, value, then_case, else_case } => {
+            inline_in(value, depth, candidates);
+            inline_in(then_case, *offset + 1, candidates);
+
+            if let Some(else_case) = else_case {
+                inline_in(else_case, depth, candidates);
+            }
+        },
+        ASTNode::While { condition, statement } => {
+            inline_in(condition, depth, candidates);
+            inline_in(statement, depth, candidates);
+        },
+        ASTNode::Let { offset: _, index, fill: _, rhs, loc: _ } => {
+            if let Some(index) = index {
+                inline_in(index, depth, candidates);
+            }
+            inline_in(rhs, depth, candidates);
+        },
+        ASTNode::Read { targets, propagate: _ } => {
+            for (_, _, index, _) in targets {
+                if let Some(index) = index {
+                    inline_in(index, depth, candidates);
+                }
+            }
+        },
+        ASTNode::GetIndex { offset: _, idx: index, loc: _ } => {
+            inline_in(index, depth, candidates);
+        },
+        ASTNode::Print { items, stderr: _ } | ASTNode::Tuple { items } => {
+            for item in items {
+                inline_in(item, depth, candidates);
+            }
+        },
+        ASTNode::Return { expr: Some(value) } | ASTNode::Destructure { offsets: _, rhs: value, loc: _ }
+            | ASTNode::Some { value } | ASTNode::Ok { value } | ASTNode::Err { message: value }
+            | ASTNode::Discard { value } | ASTNode::Try { value, propagate: _ }
+            | ASTNode::UnaryOp { op: _, value } => {
+            inline_in(value, depth, candidates);
+        },
+        ASTNode::BinaryOp { lhs, op: _, rhs } => {
+            inline_in(lhs, depth, candidates);
+            inline_in(rhs, depth, candidates);
+        },
+        ASTNode::Conditional { condition, then_value, else_value } => {
+            inline_in(condition, depth, candidates);
+            inline_in(then_value, depth, candidates);
+            inline_in(else_value, depth, candidates);
+        },
+        ASTNode::Call { name, args } => {
+            for arg in args.iter_mut() {
+                inline_in(arg, depth, candidates);
+            }
+
+            if let Some((param_count, expr)) = candidates.get(name) {
+                if *param_count == args.len() {
+                    let mut body: ASTNode = expr.clone();
+                    shift_offsets(&mut body, depth);
+
+                    *node = ASTNode::Inlined {
+                        name: std::mem::take(name),
+                        base: depth,
+                        args: std::mem::take(args),
+                        body: Box::new(body),
+                    };
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_text;
+
+    // The outline of the last function in the program once it is optimised.
+    fn optimised(text: &str, inline: bool) -> String {
+        let mut root: ASTNode = parse_text(text);
+        optimise(&mut root, inline);
+
+        let ASTNode::Toplevel { funcdefs, tests: _ } = root else { panic!("no toplevel") };
+        funcdefs.last().unwrap().to_string()
+    }
+
+    // Folds two integers, None when it is left for the walker.
+    fn integers(op: Token, lhs: i32, rhs: i32) -> Option<String> {
+        fold_binary(&op, &Value::Integer(lhs), &Value::Integer(rhs)).map(|val| val.to_string())
+    }
+
+    #[test]
+    fn fold_integers() {
+        assert_eq!(integers(Token::Add, 2, 3).as_deref(), Some("5"));
+        assert_eq!(integers(Token::Pow, 2, 10).as_deref(), Some("1024"));
+        assert_eq!(integers(Token::Shl, 1, 40).as_deref(), Some("256"));
+        assert_eq!(integers(Token::Lt, 1, 2).as_deref(), Some("true"));
+        assert_eq!(fold_unary(&Token::Sub, &Value::Integer(5)).map(|val| val.to_string()).as_deref(), Some("-5"));
+    }
+
+    #[test]
+    fn overflow_is_left_for_the_walker() {
+        assert!(integers(Token::Add, i32::MAX, 1).is_none());
+        assert!(integers(Token::Sub, i32::MIN, 1).is_none());
+        assert!(integers(Token::Mul, i32::MAX, 2).is_none());
+        assert!(integers(Token::Div, i32::MIN, -1).is_none());
+        assert!(integers(Token::Mod, i32::MIN, -1).is_none());
+        assert!(integers(Token::Div, 1, 0).is_none());
+        assert!(integers(Token::Mod, 1, 0).is_none());
+        assert!(integers(Token::Pow, 2, 31).is_none());
+        assert!(integers(Token::Pow, 2, -1).is_none());
+        assert!(fold_unary(&Token::Sub, &Value::Integer(i32::MIN)).is_none());
+    }
+
+    #[test]
+    fn strings_only_fold_equality() {
+        let (a, b) = (Value::String("a".into()), Value::String("b".into()));
+
+        assert_eq!(fold_binary(&Token::Add, &a, &b).map(|val| val.to_string()).as_deref(), Some("ab"));
+        assert_eq!(fold_binary(&Token::Neq, &a, &b).map(|val| val.to_string()).as_deref(), Some("true"));
+        assert!(fold_binary(&Token::Lt, &a, &b).is_none());
+    }
+
+    #[test]
+    fn identities() {
+        assert!(is_right_identity(&Token::Sub, &Value::Integer(0)));
+        assert!(is_right_identity(&Token::Div, &Value::Integer(1)));
+        assert!(is_right_identity(&Token::Pow, &Value::Integer(1)));
+        assert!(is_right_identity(&Token::Mul, &Value::Float(1.0)));
+        assert!(is_left_identity(&Token::Add, &Value::Integer(0)));
+        assert!(is_left_identity(&Token::Mul, &Value::Integer(1)));
+
+        // order matters for these, and x * 0 still has to evaluate x
+        assert!(!is_left_identity(&Token::Sub, &Value::Integer(0)));
+        assert!(!is_left_identity(&Token::Div, &Value::Integer(1)));
+        assert!(!is_left_identity(&Token::Shl, &Value::Integer(0)));
+        assert!(!is_right_identity(&Token::Mul, &Value::Integer(0)));
+
+        // -0.0 + 0.0 is 0.0, so adding a float zero isn't a no-op
+        assert!(!is_right_identity(&Token::Add, &Value::Float(0.0)));
+    }
+
+    #[test]
+    fn fold_expressions_and_branches() {
+        let text: &str = "func f(integer x) integer {\n    if 1 > 2 {\n        return 0;\n    } elif true {\n        return (x * 1 + 0) + (2 + 3) * 4;\n    }\n    return 2147483647 + 1;\n}\n";
+
+        assert_eq!(optimised(text, false), "\
+func f(integer x) integer
+  block
+    block
+      return
+        binary +
+          x @0
+          20
+    return
+      binary +
+        2147483647
+        1
+");
+    }
+
+    #[test]
+    fn false_loops_disappear() {
+        let text: &str = "func f() {\n    while 1 == 2 {\n        print(\"never\");\n    }\n}\n";
+
+        assert_eq!(optimised(text, false), "func f()\n  block\n    block\n");
+    }
+
+    #[test]
+    fn inline_at_frame_depth() {
+        let text: &str = "\
+func sq(integer n) integer {
+    return n * n;
+}
+
+func f(integer a) integer {
+    var integer b = 2, c = sq(b);
+    var integer d = sq(c);
+    if let some(e) = some(d) {
+        return sq(e);
+    }
+    return sq(a);
+}
+";
+
+        let outline: String = optimised(text, true);
+        let inlined: Vec<&str> = outline.lines().filter_map(|line| line.trim().trim_start_matches("= ").strip_prefix("inlined ")).collect();
+        assert_eq!(inlined, ["sq @2", "sq @3", "sq @5", "sq @4"]);
+
+        // the argument is read from the caller's frame, the body from the slot it is copied to
+        assert!(outline.contains("inlined sq @5\n            arg: e @4\n            body: binary *\n              n @5\n"), "{}", outline);
+    }
+
+    #[test]
+    fn only_small_leaves_are_inlined() {
+        let calls_user: &str = "func g(integer n) integer {\n    return n;\n}\n\nfunc h(integer n) integer {\n    return g(n) + 1;\n}\n\nfunc f() integer {\n    return h(1);\n}\n";
+        assert!(optimised(calls_user, true).contains("call h"));
+
+        // 25 and 23 nodes, either side of INLINE_THRESHOLD
+        let big: String = format!("func g(integer n) integer {{\n    return n{};\n}}\n\nfunc f() integer {{\n    return g(1);\n}}\n", " + n".repeat(12));
+        assert!(optimised(&big, true).contains("call g"));
+
+        let small: String = format!("func g(integer n) integer {{\n    return n{};\n}}\n\nfunc f() integer {{\n    return g(1);\n}}\n", " + n".repeat(11));
+        assert!(optimised(&small, true).contains("inlined g @0"));
+
+        let two_statements: &str = "func g(integer n) integer {\n    var integer m = n;\n    return m;\n}\n\nfunc f() integer {\n    return g(1);\n}\n";
+        assert!(optimised(two_statements, true).contains("call g"));
+    }
+
+    #[test]
+    fn leaf_size_counts_nodes() {
+        let root: ASTNode = parse_text("func g(integer n) integer {\n    return abs(n) * (0 - n) + 1;\n}\n");
+        let ASTNode::Toplevel { funcdefs, tests: _ } = root else { panic!("no toplevel") };
+        let ASTNode::Funcdef { body, .. } = &*funcdefs[0] else { panic!("no funcdef") };
+        let ASTNode::Block { statements, locs: _ } = &**body else { panic!("no body") };
+        let ASTNode::Return { expr: Some(expr) } = &*statements[0] else { panic!("no return") };
+
+        assert_eq!(leaf_size(expr), Some(8));
+    }
+}
@@ -4,27 +4,40 @@ use crate::{error, error::*};
 use crate::token::*;
 use crate::{properties::*};
 use crate::ast::*;
-use crate::value::Value;
+use crate::module::Module;
+use crate::value::{Value, zero_value};
+use crate::builtins::{self, BUILTINS};
 
 pub struct Parser {
+    modules: Vec<Module>,
+    module: usize,
+
     tokens: Vec<(Token, (u32, u32))>,
 
     symboltable: HashMap<String, Properties>,
 
     local_table: HashMap<String, Properties>,
 
-    current_ret_type: u8,
+    current_ret_type: u32,
+    current_func: String,
+
+    // '?' inside a test fails the test, like it ends the program in main
+    in_test: bool,
 
     idx: usize,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<(Token, (u32, u32))>) -> Parser {
+    pub fn new(modules: Vec<Module>) -> Parser {
         Parser {
-            tokens,
+            modules,
+            module: 0,
+            tokens: vec![],
             symboltable: HashMap::new(),
             local_table: HashMap::new(),
             current_ret_type: NONE,
+            current_func: String::new(),
+            in_test: false,
             idx: 0,
         }
     }
@@ -32,25 +45,59 @@ impl Parser {
     // XXX: This is a 2-pass "compiler"
     // I don't want to forward declare things.
     // I'm not as good at language design as His Worshipfulness.
-    pub fn parse_tok_stream(&mut self) -> ASTNode {
-        self.parse_func_signatures();   // Pass 1
+    // Every module gets both passes, so functions can be used across files
+    // regardless of import order.
+    // Only 'rick test' gets away without a main.
+    pub fn parse_tok_stream(&mut self, require_main: bool) -> ASTNode {
+        let mut top_level: Vec<Box<ASTNode>> = vec![];
+        let mut tests: Vec<Box<ASTNode>> = vec![];
+
+        for builtin in BUILTINS {
+            self.symboltable.insert(builtin.name.into(), builtin.properties());
+        }
+
+        for module in 0..self.modules.len() {
+            self.enter_module(module);
+            self.parse_func_signatures();   // Pass 1
+        }
+
+        if require_main && !self.symboltable.contains_key("main") {
+            let root: usize = self.modules.iter().position(|m| m.name.is_empty()).unwrap_or(0);
+            error::set_source(self.modules[root].source);
+            report_err_at((1, 1), RickError::MissingMain);
+        }
+
+        for module in 0..self.modules.len() {
+            self.enter_module(module);
+            top_level.append(&mut self.parse_program(&mut tests));   // Pass 2
+        }
+
+        ASTNode::Toplevel{ funcdefs: top_level, tests }
+    }
+
+    fn enter_module(&mut self, module: usize) {
+        self.module = module;
+        self.tokens = self.modules[module].tokens.clone();
         self.idx = 0;
-        let root: ASTNode = self.parse_program();   // Pass 2
 
-        root
+        error::set_source(self.modules[module].source);
     }
 
     fn parse_func_signatures(&mut self) {
         while self.current().0 != Token::Eof {
             if self.current().0 == Token::Func {
-                self.parse_func_type_info();
+                self.parse_func_type_info(false);
+            } else if self.current().0 == Token::Pub && self.peek() == Token::Func {
+                self.next_token();
+                self.parse_func_type_info(true);
             } else {
                 self.next_token();
             }
         }
     }
 
-    fn parse_program(&mut self) -> ASTNode {
+    #[allow(clippy::vec_box, reason = "the AST keeps its children as Vec<Box<ASTNode>>")]
+    fn parse_program(&mut self, tests: &mut Vec<Box<ASTNode>>) -> Vec<Box<ASTNode>> {
         let mut top_level: Vec<Box<ASTNode>> = vec![];
 
         while self.current().0 != Token::Eof {
@@ -58,15 +105,32 @@ impl Parser {
                 Token::Func => {
                     top_level.push(Box::new(self.parse_subdef()));
                 },
+                Token::Pub => {
+                    self.next_token();
+                    if self.current().0 != Token::Func {
+                        report_err_at(self.current().1, RickError::Expected(self.current().0, Token::Func));
+                    }
+                    top_level.push(Box::new(self.parse_subdef()));
+                },
+                // 'test' is only special here, anywhere else it is a plain name
+                Token::Identifier(id) if id == "test" && matches!(self.peek(), Token::StringLiteral(_)) => {
+                    let test: ASTNode = self.parse_test(tests);
+                    tests.push(Box::new(test));
+                },
+                Token::Import => {
+                    // Already resolved by the module loader.
+                    while self.current().0 != Token::Semicolon {
+                        self.next_token();
+                    }
+                    self.next_token();
+                },
                 _ => {
                     panic!("ERROR: unimplemented top level statement '{}'", self.current().0);
                 }
             }
         }
 
-        let t: ASTNode = ASTNode::Toplevel{ funcdefs: top_level };
-
-        t
+        top_level
     }
 
     fn parse_subdef(&mut self) -> ASTNode {
@@ -77,12 +141,24 @@ impl Parser {
         self.next_token();
 
         let mut name: String = String::new();
+        let loc: (u32, u32) = self.current().1;
         self.expect_identifier(&mut name);
+        name = self.qualify(&name);
         
-        while self.current().0 != Token::Rpar {
+        // skip the parameter list, tuple types bring their own parentheses
+        let mut depth: u32 = 0;
+        loop {
+            match self.current().0 {
+                Token::Lpar => depth += 1,
+                Token::Rpar => depth -= 1,
+                _ => {},
+            }
             self.next_token();
+
+            if depth == 0 {
+                break;
+            }
         }
-        self.next_token();
 
         if self.current().0.is_type_start() {
             self.current_ret_type = self.parse_type();
@@ -92,10 +168,11 @@ impl Parser {
 
         // TODO: proper error reporting
         let props = self.symboltable.get(&name).expect("Function not found!").clone();
+        self.current_func = name.clone();
         
         self.local_table.drain();
         for (name, tipe) in &props.params {
-            self.local_table.insert(name.clone(), Properties{ tipe: *tipe, offset: Some(self.local_table.len() as u32), params: vec![] });    
+            self.local_table.insert(name.clone(), Properties::variable(*tipe, self.local_table.len() as u32));
         }
 
         let body: ASTNode = self.parse_statement();
@@ -104,8 +181,50 @@ impl Parser {
 
         ASTNode::Funcdef {
             name,
-            params: props.params,
+            params: props.params.into_iter().zip(props.param_locs).map(|((name, tipe), loc)| (name, tipe, loc)).collect(),
             ret_type: props.tipe,
+            variadic: props.variadic,
+            source: self.modules[self.module].source,
+            loc,
+            body: Box::new(body),
+        }
+    }
+
+    // test "name" { ... }
+    // Only 'rick test' runs these. The body is checked like a procedure
+    // without parameters.
+    fn parse_test(&mut self, tests: &[Box<ASTNode>]) -> ASTNode {
+        self.next_token();
+
+        let loc: (u32, u32) = self.current().1;
+        let name: String = match self.current().0 {
+            Token::StringLiteral(s) => self.qualify(&s),
+            found => report_err_at(loc, RickError::Expected(found, Token::StringLiteral("test name".into()))),
+        };
+        self.next_token();
+
+        for test in tests {
+            if matches!(&**test, ASTNode::Funcdef { name: other, .. } if *other == name) {
+                report_err_at(loc, RickError::DuplicateTest(name));
+            }
+        }
+
+        self.current_func = name.clone();
+        self.current_ret_type = NONE;
+        self.in_test = true;
+        self.local_table.drain();
+
+        let body: ASTNode = self.parse_block();
+
+        self.in_test = false;
+
+        ASTNode::Funcdef {
+            name,
+            params: vec![],
+            ret_type: NONE,
+            variadic: false,
+            source: self.modules[self.module].source,
+            loc,
             body: Box::new(body),
         }
     }
@@ -114,51 +233,57 @@ impl Parser {
         match self.current().0 {
             Token::Lbrace => {
                 let block: ASTNode = self.parse_block();
-                return block;
+                block
             },
             Token::If => {
                 let if_statement: ASTNode = self.parse_if();
-                return if_statement;
+                if_statement
             },
             Token::While => {
                 let while_statement: ASTNode = self.parse_while();
-                return while_statement;
+                while_statement
             },
             Token::Let => {
                 let assign_statement: ASTNode = self.parse_assign();
                 self.expect(Token::Semicolon);
 
-                return assign_statement;
+                assign_statement
             },
             Token::Var => {
                 let vardef_statement = self.parse_vardef();
                 self.expect(Token::Semicolon);
 
-                return vardef_statement;
+                vardef_statement
             },
             Token::Identifier(_) => {
                 let call_statement: ASTNode = self.parse_call();
                 self.expect(Token::Semicolon);
 
-                return call_statement;
+                call_statement
             },
             Token::Read => {
                 let read_statement: ASTNode = self.parse_read();
                 self.expect(Token::Semicolon);
 
-                return read_statement;
+                read_statement
             },
-            Token::Print => {
+            Token::Print | Token::Eprint => {
                 let print_statement: ASTNode = self.parse_print();
                 self.expect(Token::Semicolon);
 
-                return print_statement;
+                print_statement
             },
             Token::Return => {
                 let return_statement: ASTNode = self.parse_return();
                 self.expect(Token::Semicolon);
 
-                return return_statement;
+                return_statement
+            },
+            Token::Assert => {
+                let assert_statement: ASTNode = self.parse_assert();
+                self.expect(Token::Semicolon);
+
+                assert_statement
             },
             _ => {
                 // TODO: implement proper error message
@@ -171,11 +296,13 @@ impl Parser {
 
     fn parse_block(&mut self) -> ASTNode {
         let mut stats: Vec<Box<ASTNode>> = vec![];
+        let mut locs: Vec<(u32, u32)> = vec![];
 
         self.expect(Token::Lbrace);
         let local_size: u32 = self.local_table.len() as u32;
 
         while self.current().0 != Token::Rbrace {
+            locs.push(self.current().1);
             stats.push( Box::new(self.parse_statement()) );
         }
 
@@ -186,40 +313,39 @@ impl Parser {
 
         ASTNode::Block {
             statements: stats,
+            locs,
         }
     }
 
     fn parse_if(&mut self) -> ASTNode {
-        let cond: Box<ASTNode>;
-        let stat: Box<ASTNode>;
-
         let mut branches: Vec<(Box<ASTNode>, Box<ASTNode>)> = vec![];
         let mut else_case: Option<Box<ASTNode>> = None;
 
-        let mut cond_type: u8 = NONE;
+        let mut cond_type: u32 = NONE;
 
         self.expect(Token::If);
 
-        cond = Box::new(self.parse_expr(&mut cond_type));
+        if self.current().0 == Token::Let {
+            return self.parse_if_let();
+        }
+
+        let cond: Box<ASTNode> = Box::new(self.parse_expr(&mut cond_type));
 
         if cond_type != BOOLEAN {
             panic!("Expected boolean, found {} for if condition", type_string(cond_type));
         }
 
-        stat = Box::new(self.parse_statement());
+        let stat: Box<ASTNode> = Box::new(self.parse_statement());
         branches.push((cond, stat));
 
         while self.current().0 == Token::Elif {
-            let else_cond: Box<ASTNode>;
-            let else_stat: Box<ASTNode>;
-
             self.next_token();
-            else_cond = Box::new(self.parse_expr(&mut cond_type));
+            let else_cond: Box<ASTNode> = Box::new(self.parse_expr(&mut cond_type));
 
             if cond_type != BOOLEAN {
                 panic!("Expected boolean, found {} for elif condition", type_string(cond_type));
             }
-            else_stat = Box::new(self.parse_statement());
+            let else_stat: Box<ASTNode> = Box::new(self.parse_statement());
 
             branches.push((else_cond, else_stat));
         }
@@ -235,19 +361,59 @@ impl Parser {
         }
     }
 
+    // if let some(x) = expr statement [else statement]
+    fn parse_if_let(&mut self) -> ASTNode {
+        let mut name: String = String::new();
+        let mut value_type: u32 = NONE;
+        let mut else_case: Option<Box<ASTNode>> = None;
+
+        self.expect(Token::Let);
+        self.expect(Token::Some);
+        self.expect(Token::Lpar);
+        self.expect_identifier(&mut name);
+        self.expect(Token::Rpar);
+        self.expect(Token::Assign);
+
+        let loc: (u32, u32) = self.current().1;
+        let value: ASTNode = self.parse_expr(&mut value_type);
+
+        if value_type & OPTIONAL == 0 || value_type == OPTIONAL {
+            report_err_at(loc, RickError::NotOptional(value_type));
+        }
+
+        let offset: u32 = self.local_table.len() as u32;
+        if self.local_table.insert(name.clone(), Properties::variable(value_type & !OPTIONAL, offset)).is_some() {
+            // TODO: better error reporting
+            panic!("Error: Multiple definition of local variable.");
+        }
+
+        let then_case: ASTNode = self.parse_statement();
+        self.local_table.remove(&name);
+
+        if self.current().0 == Token::Else {
+            self.next_token();
+            else_case = Some(Box::new(self.parse_statement()));
+        }
+
+        ASTNode::IfLet {
+            offset,
+            value: Box::new(value),
+            then_case: Box::new(then_case),
+            else_case,
+        }
+    }
+
     fn parse_while(&mut self) -> ASTNode {
-        let expr: Box<ASTNode>;
-        let stat: Box<ASTNode>;
-        let mut cond_type: u8 = NONE;
+        let mut cond_type: u32 = NONE;
         
         self.expect(Token::While);
 
-        expr = Box::new(self.parse_expr(&mut cond_type));
+        let expr: Box<ASTNode> = Box::new(self.parse_expr(&mut cond_type));
         if cond_type != BOOLEAN {
             panic!("Expected boolean, found {} for while condition", type_string(cond_type));
         }
 
-        stat = Box::new(self.parse_statement());
+        let stat: Box<ASTNode> = Box::new(self.parse_statement());
 
         ASTNode::While {
             condition: expr,
@@ -257,22 +423,26 @@ impl Parser {
 
     fn parse_assign(&mut self) -> ASTNode {
         let mut name: String = String::new();
-        let props: Properties;
-        let mut tipe: u8;
+        let mut tipe: u32;
         let mut index: Option<Box<ASTNode>> = None;
-        let mut is_array: bool = false;
+        let mut fill: Option<Value> = None;
         let right_expr: Box<ASTNode>;
 
         self.expect(Token::Let);
 
+        if self.current().0 == Token::Lpar {
+            return self.parse_destructure();
+        }
+
+        let loc: (u32, u32) = self.current().1;
         self.expect_identifier(&mut name);
 
         // TODO: better error reporting
-        props = self.local_table.get(&name).expect("Undefined variable").clone();
+        let props: Properties = self.local_table.get(&name).expect("Undefined variable").clone();
         tipe = props.tipe;
 
         if self.current().0 == Token::Lbrack {
-            if tipe | ARRAY == 0 {
+            if !is_array_type(tipe) {
                 // TODO: better error reporting
                 panic!("Cannot index non-array type");
             }
@@ -284,79 +454,158 @@ impl Parser {
 
         self.expect(Token::Assign);
 
-        let mut rhs: u8 = NONE;
+        let mut rhs: u32 = NONE;
 
         if self.current().0 == Token::Array {
-            if tipe | ARRAY == 0 {
+            if !is_array_type(tipe) {
                 // TODO: better error reporting
                 panic!("Can't assign array to non-array variable");
             }
-            is_array = true;
+            fill = match zero_value(tipe & !ARRAY) {
+                Some(zero) => Some(zero),
+                None => report_err_at(self.current().1, RickError::NoDefaultElement(tipe & !ARRAY)),
+            };
             self.next_token();
 
-            let mut simp_type: u8 = NONE;
-            right_expr = Box::new(self.parse_simple(&mut simp_type));
+            let mut simp_type: u32 = NONE;
+            right_expr = Box::new(self.parse_bit_or(&mut simp_type));
 
-            rhs = simp_type | ARRAY;
+            if simp_type != INTEGER {
+                // TODO: better error reporting
+                panic!("Array size must be an integer, found {}", type_string(simp_type));
+            }
         } else {
+            let loc: (u32, u32) = self.current().1;
             right_expr = Box::new(self.parse_expr(&mut rhs));
             
-            if rhs != tipe {
-                // TODO: better error reporting
-                dbg!(type_string(rhs));
-                dbg!(type_string(tipe));
-                panic!("Type mismatch in assignment.");
+            if !is_assignable(tipe, rhs) {
+                report_err_at(loc, RickError::TypeMismatch(tipe, rhs));
             }
         }
 
         ASTNode::Let {
             offset: props.offset.unwrap(),
             index,
-            is_array,
+            fill,
             rhs: right_expr,
+            loc,
         }
     }
 
-    fn parse_vardef(&mut self) -> ASTNode {
-        let mut id: String = String::new(); 
-        let tipe: u8;
-        let mut names: Vec<String> = vec![];
+    // let (q, r) = divmod(a, b);  '_' skips an element
+    fn parse_destructure(&mut self) -> ASTNode {
+        let mut offsets: Vec<Option<u32>> = vec![];
+        let mut types: Vec<u32> = vec![];
+        let mut rhs: u32 = NONE;
 
-        self.expect(Token::Var);
+        let pattern_loc: (u32, u32) = self.current().1;
+        self.expect(Token::Lpar);
 
-        tipe = self.parse_type();
+        loop {
+            let mut name: String = String::new();
+            self.expect_identifier(&mut name);
 
-        self.expect_identifier(&mut id);
-        names.push(id.clone());
+            if name == "_" {
+                offsets.push(None);
+                types.push(NONE);
+            } else {
+                // TODO: better error reporting
+                let props = self.local_table.get(&name).expect("Undefined variable").clone();
+                offsets.push(props.offset);
+                types.push(props.tipe);
+            }
 
-        if self.local_table.insert(id.clone(), Properties {tipe, offset: Some(self.local_table.len() as u32), params: vec![]}).is_some() {
-            // TODO: better error reporting
-            panic!("Error: Multiple definition of local variable.");
+            if self.current().0 != Token::Comma {
+                break;
+            }
+            self.next_token();
         }
 
-        while self.current().0 == Token::Comma {
-            self.next_token();
+        self.expect(Token::Rpar);
+        self.expect(Token::Assign);
+
+        let loc: (u32, u32) = self.current().1;
+        let right_expr: ASTNode = self.parse_expr(&mut rhs);
+
+        if rhs & (TUPLE | ARRAY | OPTIONAL | RESULT) != TUPLE || tuple_elements(rhs).len() != offsets.len() {
+            report_err_at(loc, RickError::DestructureMismatch(rhs, offsets.len()));
+        }
+
+        for (expected, found) in types.iter().zip(tuple_elements(rhs)) {
+            if *expected != NONE && !is_assignable(*expected, found) {
+                report_err_at(loc, RickError::TypeMismatch(*expected, found));
+            }
+        }
+
+        ASTNode::Destructure {
+            offsets,
+            rhs: Box::new(right_expr),
+            loc: pattern_loc,
+        }
+    }
+
+    // var integer a, b = 2;  or  var a = 1, s = "x";  Without a type every
+    // variable needs an initialiser to take its type from.
+    fn parse_vardef(&mut self) -> ASTNode {
+        let mut declared: Option<u32> = None;
+        let mut vars: Vec<Declaration> = vec![];
+
+        self.expect(Token::Var);
+
+        if self.current().0.is_type_start() {
+            declared = Some(self.parse_type());
+        }
+
+        loop {
+            let mut id: String = String::new();
+            let mut init: Option<Box<ASTNode>> = None;
+            let mut tipe: u32 = declared.unwrap_or(NONE);
+
+            let decl_loc: (u32, u32) = self.current().1;
             self.expect_identifier(&mut id);
-            names.push(id.clone());
 
-            if self.local_table.insert(id.clone(), Properties {tipe, offset: Some(self.local_table.len() as u32), params: vec![]}).is_some() {
+            if self.current().0 == Token::Assign {
+                let mut rhs: u32 = NONE;
+                self.next_token();
+
+                // the variable only comes into scope after its initialiser
+                let loc: (u32, u32) = self.current().1;
+                init = Some(Box::new(self.parse_expr(&mut rhs)));
+
+                if declared.is_none() {
+                    if rhs == OPTIONAL || rhs == RESULT {
+                        report_err_at(loc, RickError::CannotInfer(rhs));
+                    }
+                    tipe = rhs;
+                } else if !is_assignable(tipe, rhs) {
+                    report_err_at(loc, RickError::TypeMismatch(tipe, rhs));
+                }
+            } else if declared.is_none() {
+                report_err_at(self.current().1, RickError::Expected(self.current().0, Token::Assign));
+            }
+
+            if self.local_table.insert(id.clone(), Properties::variable(tipe, self.local_table.len() as u32)).is_some() {
                 // TODO: better error reporting
                 panic!("Error: Multiple definition of local variable.");
             }
+            vars.push((id, tipe, init, decl_loc));
+
+            if self.current().0 != Token::Comma {
+                break;
+            }
+            self.next_token();
         }
 
         ASTNode::VarDef {
-            tipe,
-            names
+            vars
         }
     }
 
     fn parse_index(&mut self) -> ASTNode {
-        let index: ASTNode;
-        let mut index_type: u8 = NONE;
+        let mut index_type: u32 = NONE;
         self.expect(Token::Lbrack);
 
-        index = self.parse_simple(&mut index_type);
+        let index: ASTNode = self.parse_bit_or(&mut index_type);
 
         if index_type != INTEGER {
             // TODO: better error reporting
@@ -369,68 +618,92 @@ impl Parser {
     }
 
     fn parse_call(&mut self) -> ASTNode {
-        let mut id: String = String::new();
-        self.expect_identifier(&mut id);
+        let (id, props) = self.expect_function_name();
+        let mut tipe: u32 = props.tipe;
 
-        if !self.symboltable.contains_key(&id) {
+        if tipe & !FUNC != 0 && (tipe & RESULT == 0 || self.peek_past_arglist() != Token::Question) {
             // TODO: better error reporting
-            panic!("Identifier '{}' does not exist as a function", id);
+            panic!("'{}' is not a procedure", id);
         }
 
-        let props = self.symboltable.get(&id).unwrap().clone();
-        if props.tipe & !FUNC != 0 {
-            // TODO: better error reporting
-            panic!("'{}' is not a procedure", id);
+        let args: Vec<Box<ASTNode>> = self.parse_arglist(props, id.clone(), &mut tipe);
+        let call: ASTNode = ASTNode::Call {
+            name: id,
+            args,
+        };
+
+        if tipe & !FUNC == NONE {
+            return call;
         }
 
-        let args: Vec<Box<ASTNode>> = self.parse_arglist(props, id.clone());
+        // f(...)?; only checks for an error, the value itself is thrown away
+        let propagate: bool = self.try_propagates(self.current().1);
+        self.next_token();
 
-        ASTNode::Call {
-            name: id,
-            args: args,
+        ASTNode::Discard {
+            value: Box::new(ASTNode::Try { value: Box::new(call), propagate }),
         }
     }
 
+    // read(a, b[i], c)
+    // Each target takes the next word of input, an array without an index
+    // takes one for each of its elements.
     fn parse_read(&mut self) -> ASTNode {
-        let mut id: String = String::new();
-        let output: ASTNode;
+        let mut targets: Vec<ReadTarget> = vec![];
+        let mut propagate: bool = false;
 
         self.expect(Token::Read);
         self.expect(Token::Lpar);
 
-        self.expect_identifier(&mut id);
+        loop {
+            let mut id: String = String::new();
+            let mut index: Option<Box<ASTNode>> = None;
 
-        // TODO: better error reporting
-        let props: &Properties = self.local_table.get(&id).expect("Variable doesnt exist!");
-        let tipe: u8 = props.tipe.clone();
-        let offset: u32 = props.offset.expect("Must read into a variable");
+            let loc: (u32, u32) = self.current().1;
+            self.expect_identifier(&mut id);
 
-        if self.current().0 == Token::Lbrack {
-            if props.tipe & ARRAY == 0 {
+            // TODO: better error reporting
+            let props: &Properties = self.local_table.get(&id).expect("Variable doesnt exist!");
+            let tipe: u32 = props.tipe;
+            let offset: u32 = props.offset.expect("Must read into a variable");
+
+            if tipe & (TUPLE | OPTIONAL | RESULT | FILE) != 0 {
                 // TODO: proper error reporting
-                panic!("Not an array!");
+                panic!("Cannot read into a {}", type_string(tipe));
             }
-            let idx: ASTNode = self.parse_index();
-            output = ASTNode::Read { offset, tipe, index: Some(Box::new(idx)) };
-        } else {
-            if props.tipe & ARRAY != 0 {
-                // TODO: better error reporting
-                panic!("Index array before reading input");
+
+            if self.current().0 == Token::Lbrack {
+                if !is_array_type(tipe) {
+                    // TODO: proper error reporting
+                    panic!("Not an array!");
+                }
+                index = Some(Box::new(self.parse_index()));
+            }
+
+            targets.push((offset, tipe, index, loc));
+
+            if self.current().0 != Token::Comma {
+                break;
             }
-            // TODO: 'get' variable with id 
-            output = ASTNode::Read { offset, tipe, index: None };
+            self.next_token();
         }
 
         self.expect(Token::Rpar);
 
-        output
+        if self.current().0 == Token::Question {
+            propagate = self.try_propagates(self.current().1);
+            self.next_token();
+        }
+
+        ASTNode::Read { targets, propagate }
     }
 
     fn parse_print(&mut self) -> ASTNode {
-        let mut expr_type: u8 = NONE;
+        let mut expr_type: u32 = NONE;
         let mut items: Vec<Box<ASTNode>> = vec![];
+        let stderr: bool = self.current().0 == Token::Eprint;
 
-        self.expect(Token::Print);
+        self.next_token();
         self.expect(Token::Lpar);
 
         loop {
@@ -448,18 +721,56 @@ impl Parser {
 
         ASTNode::Print {
             items,
+            stderr,
+        }
+    }
+
+    // assert(condition) or assert(condition, message)
+    fn parse_assert(&mut self) -> ASTNode {
+        let loc: (u32, u32) = self.current().1;
+        let mut cond_type: u32 = NONE;
+        let mut message: Option<Box<ASTNode>> = None;
+
+        self.expect(Token::Assert);
+        self.expect(Token::Lpar);
+
+        let cond_loc: (u32, u32) = self.current().1;
+        let condition: ASTNode = self.parse_expr(&mut cond_type);
+        if cond_type != BOOLEAN {
+            report_err_at(cond_loc, RickError::TypeMismatch(BOOLEAN, cond_type));
+        }
+
+        if self.current().0 == Token::Comma {
+            self.next_token();
+
+            let mut message_type: u32 = NONE;
+            let message_loc: (u32, u32) = self.current().1;
+            message = Some(Box::new(self.parse_expr(&mut message_type)));
+
+            if message_type != STRING {
+                report_err_at(message_loc, RickError::TypeMismatch(STRING, message_type));
+            }
+        }
+
+        self.expect(Token::Rpar);
+
+        ASTNode::Assert {
+            condition: Box::new(condition),
+            message,
+            source: self.modules[self.module].source,
+            loc,
         }
     }
 
     fn parse_return(&mut self) -> ASTNode {
-        let mut expr_type: u8 = NONE;
+        let mut expr_type: u32 = NONE;
         let mut ret_expr: Option<Box<ASTNode>> = None;
 
         self.expect(Token::Return);
 
         if self.current().0.start_expression() {
             ret_expr = Some(Box::new(self.parse_expr(&mut expr_type)));
-            if expr_type != self.current_ret_type {
+            if !is_assignable(self.current_ret_type, expr_type) {
                 // TODO: Proper error reporting
                 panic!("ERROR: incorrect type for return expression.");
             }
@@ -472,17 +783,16 @@ impl Parser {
         ASTNode::Return{ expr: ret_expr }
     }
 
-    fn parse_expr(&mut self, parent_type: &mut u8) -> ASTNode {
+    fn parse_expr(&mut self, parent_type: &mut u32) -> ASTNode {
         let mut output: ASTNode;
-        let mut rhs: u8 = NONE;
+        let mut rhs: u32 = NONE;
 
-        output = self.parse_simple(parent_type);
+        output = self.parse_bit_or(parent_type);
 
-        if self.current().0.is_ordering_op() {
-            if !is_numeric_type(*parent_type) {
+        if self.current().0.is_ordering_op()
+            && !is_ordered_type(*parent_type) {
                 panic!("Expected numeric type, found {}", type_string(*parent_type));
             }
-        }
 
         if self.current().0.is_relational_op() {
             let op = self.current().0;
@@ -497,7 +807,7 @@ impl Parser {
             if is_numeric_type(*parent_type) && !is_numeric_type(rhs) {
                 panic!("Expected numeric type, found {} type", type_string(rhs));
             } 
-            if !is_numeric_type(*parent_type) && *parent_type != rhs {
+            if !is_numeric_type(*parent_type) && !is_assignable(*parent_type, rhs) && !is_assignable(rhs, *parent_type) {
                 panic!("Expected {} type, found {} type", type_string(*parent_type), type_string(rhs));
             }
 
@@ -507,206 +817,449 @@ impl Parser {
         output
     }
 
-    fn parse_simple(&mut self, parent_type: &mut u8) -> ASTNode {
+    // Bitwise operators sit between comparisons and arithmetic, loosest first:
+    // |, ^, &, then the shifts. Only integers are allowed on either side.
+    fn parse_bit_or(&mut self, parent_type: &mut u32) -> ASTNode {
         let mut output: ASTNode;
-        let mut rhs: u8 = NONE;
-
-        if self.current().0 == Token::Sub {
-            self.next_token();
-            output = ASTNode::UnaryOp { op: Token::Sub, value: Box::new(self.parse_term(parent_type)) };
-        } else {
-            output = self.parse_term(parent_type);
-        }
 
-        if self.current().0 == Token::Or {
-            if *parent_type != BOOLEAN {
-                panic!("Expected boolean type, found {}", type_string(*parent_type));
-            }
-        }
+        output = self.parse_bit_xor(parent_type);
 
-        while self.current().0.is_additive_op() {
+        while self.current().0 == Token::BitOr {
             let op = self.current().0;
+            let mut rhs: u32 = NONE;
+
+            self.expect_integer_operand(*parent_type);
             self.next_token();
 
+            let loc: (u32, u32) = self.current().1;
             output = ASTNode::BinaryOp {
                         lhs: Box::new(output),
                         op,
-                        rhs: Box::new(self.parse_term(&mut rhs))
+                        rhs: Box::new(self.parse_bit_xor(&mut rhs))
                     };
 
-            if *parent_type != rhs {
-                panic!("Expected {} type, found {} type", type_string(*parent_type), type_string(rhs));
+            if rhs != INTEGER {
+                report_err_at(loc, RickError::TypeMismatch(INTEGER, rhs));
             }
         }
 
         output
     }
 
-    fn parse_term(&mut self, parent_type: &mut u8) -> ASTNode {
+    fn parse_bit_xor(&mut self, parent_type: &mut u32) -> ASTNode {
         let mut output: ASTNode;
-        let mut rhs: u8 = NONE;
-
-        output = self.parse_factor(parent_type);
 
-        if self.current().0 == Token::And {
-            if *parent_type != BOOLEAN {
-                // TODO: better error reporting
-                panic!("Expected boolean type, found {} type", type_string(*parent_type));
-            }
-        }
+        output = self.parse_bit_and(parent_type);
 
-        while self.current().0.is_multiplicative_op() {
+        while self.current().0 == Token::BitXor {
             let op = self.current().0;
+            let mut rhs: u32 = NONE;
+
+            self.expect_integer_operand(*parent_type);
             self.next_token();
 
+            let loc: (u32, u32) = self.current().1;
             output = ASTNode::BinaryOp {
                         lhs: Box::new(output),
                         op,
-                        rhs: Box::new(self.parse_factor(&mut rhs))
+                        rhs: Box::new(self.parse_bit_and(&mut rhs))
                     };
 
-            if rhs != *parent_type {
-                // TODO: better error reporting
-                panic!("Expected {} type, found {} type", type_string(*parent_type), type_string(rhs));
+            if rhs != INTEGER {
+                report_err_at(loc, RickError::TypeMismatch(INTEGER, rhs));
             }
         }
 
         output
     }
 
-    fn parse_factor(&mut self, parent_type: &mut u8) -> ASTNode {
+    fn parse_bit_and(&mut self, parent_type: &mut u32) -> ASTNode {
         let mut output: ASTNode;
-        let mut rhs: u8 = NONE;
 
-        output = self.parse_base(parent_type);
-
-        if self.current().0.is_exponent_op() {
-            if parent_type != &INTEGER {
-                panic!("Expected {} type, found {} type", type_string(INTEGER), type_string(*parent_type));
-            }
-        }
+        output = self.parse_shift(parent_type);
 
-        while self.current().0.is_exponent_op() {
+        while self.current().0 == Token::BitAnd {
             let op = self.current().0;
+            let mut rhs: u32 = NONE;
+
+            self.expect_integer_operand(*parent_type);
             self.next_token();
 
+            let loc: (u32, u32) = self.current().1;
             output = ASTNode::BinaryOp {
                         lhs: Box::new(output),
                         op,
-                        rhs: Box::new(self.parse_base(&mut rhs))
+                        rhs: Box::new(self.parse_shift(&mut rhs))
                     };
 
             if rhs != INTEGER {
-                panic!("Expected {} type, found {} type", type_string(INTEGER), type_string(rhs));
+                report_err_at(loc, RickError::TypeMismatch(INTEGER, rhs));
             }
         }
 
         output
     }
 
-    fn parse_base(&mut self, parent_type: &mut u8) -> ASTNode {
-        match self.current().0 {
-            Token::Identifier(_) => {
-                let mut id: String = String::new();
-                self.expect_identifier(&mut id);
+    fn parse_shift(&mut self, parent_type: &mut u32) -> ASTNode {
+        let mut output: ASTNode;
 
-                if self.current().0 == Token::Lpar {
-                    // TODO: proper error reportings
-                    let props = self.symboltable.get(&id).expect("Variable does not exist!").clone();
-                    let tipe = props.tipe;
-                    if props.tipe & FUNC == 0 {
-                        // TODO: better error reporting
-                        panic!("{} is not a callable", id);
-                    }
-                    let args: Vec<Box<ASTNode>> = self.parse_arglist(props, id.clone());
-                    *parent_type = tipe & !FUNC;
+        output = self.parse_simple(parent_type);
 
-                    return ASTNode::Call {
-                        name: id,
-                        args,
+        while self.current().0.is_shift_op() {
+            let op = self.current().0;
+            let mut rhs: u32 = NONE;
+
+            self.expect_integer_operand(*parent_type);
+            self.next_token();
+
+            let loc: (u32, u32) = self.current().1;
+            output = ASTNode::BinaryOp {
+                        lhs: Box::new(output),
+                        op,
+                        rhs: Box::new(self.parse_simple(&mut rhs))
                     };
 
-                } else if self.current().0 == Token::Lbrack {
-                    let props = self.local_table.get(&id).expect("Variable does not exist!").clone();
-                    if props.tipe & ARRAY == 0 {
-                        // TODO: proper error reporting
-                        panic!("Trying to index non-array '{}'", id);
-                    }
-                    
-                    *parent_type = props.tipe & !ARRAY;
+            if rhs != INTEGER {
+                report_err_at(loc, RickError::TypeMismatch(INTEGER, rhs));
+            }
+        }
 
-                    return ASTNode::GetIndex {
-                        offset: props.offset.unwrap(),
-                        idx: Box::new(self.parse_index())
-                    }
+        output
+    }
 
-                } else {
+    fn parse_simple(&mut self, parent_type: &mut u32) -> ASTNode {
+        let mut output: ASTNode;
+        let mut rhs: u32 = NONE;
+
+        if self.current().0 == Token::Sub {
+            self.next_token();
+            output = ASTNode::UnaryOp { op: Token::Sub, value: Box::new(self.parse_term(parent_type)) };
+        } else {
+            output = self.parse_term(parent_type);
+        }
+
+        if self.current().0 == Token::Or
+            && *parent_type != BOOLEAN {
+                panic!("Expected boolean type, found {}", type_string(*parent_type));
+            }
+
+        while self.current().0.is_additive_op() {
+            let op = self.current().0;
+            self.next_token();
+
+            output = ASTNode::BinaryOp {
+                        lhs: Box::new(output),
+                        op,
+                        rhs: Box::new(self.parse_term(&mut rhs))
+                    };
+
+            if *parent_type != rhs {
+                panic!("Expected {} type, found {} type", type_string(*parent_type), type_string(rhs));
+            }
+        }
+
+        output
+    }
+
+    fn parse_term(&mut self, parent_type: &mut u32) -> ASTNode {
+        let mut output: ASTNode;
+        let mut rhs: u32 = NONE;
+
+        output = self.parse_factor(parent_type);
+
+        if self.current().0 == Token::And
+            && *parent_type != BOOLEAN {
+                // TODO: better error reporting
+                panic!("Expected boolean type, found {} type", type_string(*parent_type));
+            }
+
+        while self.current().0.is_multiplicative_op() {
+            let op = self.current().0;
+            self.next_token();
+
+            output = ASTNode::BinaryOp {
+                        lhs: Box::new(output),
+                        op,
+                        rhs: Box::new(self.parse_factor(&mut rhs))
+                    };
+
+            if rhs != *parent_type {
+                // TODO: better error reporting
+                panic!("Expected {} type, found {} type", type_string(*parent_type), type_string(rhs));
+            }
+        }
+
+        output
+    }
+
+    fn parse_factor(&mut self, parent_type: &mut u32) -> ASTNode {
+        let mut output: ASTNode;
+        let mut rhs: u32 = NONE;
+
+        output = self.parse_postfix(parent_type);
+
+        if self.current().0.is_exponent_op()
+            && parent_type != &INTEGER {
+                panic!("Expected {} type, found {} type", type_string(INTEGER), type_string(*parent_type));
+            }
+
+        while self.current().0.is_exponent_op() {
+            let op = self.current().0;
+            self.next_token();
+
+            output = ASTNode::BinaryOp {
+                        lhs: Box::new(output),
+                        op,
+                        rhs: Box::new(self.parse_postfix(&mut rhs))
+                    };
+
+            if rhs != INTEGER {
+                panic!("Expected {} type, found {} type", type_string(INTEGER), type_string(rhs));
+            }
+        }
+
+        output
+    }
+
+    // expr? unwraps a result, or hands the error back to the caller
+    fn parse_postfix(&mut self, parent_type: &mut u32) -> ASTNode {
+        let mut output: ASTNode;
+
+        output = self.parse_base(parent_type);
+
+        while self.current().0 == Token::Question {
+            let loc: (u32, u32) = self.current().1;
+
+            if *parent_type & RESULT == 0 {
+                report_err_at(loc, RickError::NotResult(*parent_type));
+            }
+            let propagate: bool = self.try_propagates(loc);
+            self.next_token();
+
+            *parent_type &= !RESULT;
+            output = ASTNode::Try {
+                value: Box::new(output),
+                propagate,
+            };
+        }
+
+        output
+    }
+
+    fn parse_base(&mut self, parent_type: &mut u32) -> ASTNode {
+        match self.current().0 {
+            Token::Identifier(_) => {
+                if matches!(self.peek(), Token::Lpar | Token::DoubleColon) {
+                    let (id, props) = self.expect_function_name();
+                    let mut tipe = props.tipe & !FUNC;
+                    if props.tipe & FUNC == 0 {
+                        // TODO: better error reporting
+                        panic!("{} is not a callable", id);
+                    }
+                    let args: Vec<Box<ASTNode>> = self.parse_arglist(props, id.clone(), &mut tipe);
+                    *parent_type = tipe;
+
+                    return ASTNode::Call {
+                        name: id,
+                        args,
+                    };
+                }
+
+                let mut id: String = String::new();
+                let loc: (u32, u32) = self.current().1;
+                self.expect_identifier(&mut id);
+
+                if self.current().0 == Token::Lbrack {
+                    let props = self.local_table.get(&id).expect("Variable does not exist!").clone();
+                    if props.tipe == STRING {
+                        *parent_type = CHAR;
+                    } else if !is_array_type(props.tipe) {
+                        // TODO: proper error reporting
+                        panic!("Trying to index non-array '{}'", id);
+                    } else {
+                        *parent_type = props.tipe & !ARRAY;
+                    }
+
+                    ASTNode::GetIndex {
+                        offset: props.offset.unwrap(),
+                        idx: Box::new(self.parse_index()),
+                        loc,
+                    }
+
+                } else {
                     let props = self.local_table.get(&id).expect("Variable does not exist!").clone();
                     // TODO: better error reporting
                     *parent_type = props.tipe;
 
-                    return ASTNode::GetVar {
+                    ASTNode::GetVar {
                         name: id,
                         offset: props.offset.unwrap(),
-                    };
+                        loc,
+                    }
                 }
             },
             Token::FloatLiteral(f) => {
                 self.next_token();
                 *parent_type = FLOAT;
 
-                return ASTNode::Value {
+                ASTNode::Value {
                     val: Value::Float(f)
-                };
+                }
             },
             Token::IntegerLiteral(i) => {
                 self.next_token();
                 *parent_type = INTEGER;
 
-                return ASTNode::Value {
+                ASTNode::Value {
                     val: Value::Integer(i)
-                };
+                }
             },
             Token::StringLiteral(s) => {
                 self.next_token();
                 *parent_type = STRING;
 
-                return ASTNode::Value {
+                ASTNode::Value {
                     val: Value::String(s)
-                };
+                }
+            },
+            Token::CharLiteral(c) => {
+                self.next_token();
+                *parent_type = CHAR;
+
+                ASTNode::Value {
+                    val: Value::Char(c)
+                }
             },
             Token::Lpar => {
-                let output: ASTNode;
+                let mut output: ASTNode;
 
                 self.next_token();
                 output = self.parse_expr(parent_type);
+
+                if self.current().0 == Token::Comma {
+                    let mut items: Vec<Box<ASTNode>> = vec![Box::new(output)];
+                    let mut types: Vec<u32> = vec![*parent_type];
+
+                    while self.current().0 == Token::Comma {
+                        let mut item_type: u32 = NONE;
+                        self.next_token();
+
+                        items.push(Box::new(self.parse_expr(&mut item_type)));
+                        types.push(item_type);
+                    }
+
+                    *parent_type = tuple_type(types);
+                    output = ASTNode::Tuple { items };
+                }
+
                 self.expect(Token::Rpar);
 
-                return output;
+                output
             },
             Token::Negate => {
                 self.next_token();
 
-                return ASTNode::UnaryOp {
+                ASTNode::UnaryOp {
                     op: Token::Negate,
-                    value: Box::new(self.parse_base(parent_type))
-                };
+                    value: Box::new(self.parse_postfix(parent_type))
+                }
+            },
+            Token::BitNot => {
+                self.next_token();
+                let loc: (u32, u32) = self.current().1;
+                let output: ASTNode = self.parse_postfix(parent_type);
+
+                if *parent_type != INTEGER {
+                    report_err_at(loc, RickError::TypeMismatch(INTEGER, *parent_type));
+                }
+
+                ASTNode::UnaryOp {
+                    op: Token::BitNot,
+                    value: Box::new(output)
+                }
+            },
+            Token::None => {
+                self.next_token();
+                *parent_type = OPTIONAL;
+
+                ASTNode::Value {
+                    val: Value::Optional(None),
+                }
+            },
+            Token::Some => {
+                self.next_token();
+                self.expect(Token::Lpar);
+
+                let loc: (u32, u32) = self.current().1;
+                let output: ASTNode = self.parse_expr(parent_type);
+
+                if *parent_type & (OPTIONAL | RESULT) != 0 || *parent_type == NONE {
+                    report_err_at(loc, RickError::NestedOptional(*parent_type));
+                }
+                *parent_type |= OPTIONAL;
+
+                self.expect(Token::Rpar);
+
+                ASTNode::Some {
+                    value: Box::new(output),
+                }
+            },
+            Token::Ok => {
+                self.next_token();
+                self.expect(Token::Lpar);
+
+                let loc: (u32, u32) = self.current().1;
+                let output: ASTNode = self.parse_expr(parent_type);
+
+                if *parent_type & RESULT != 0 || *parent_type == NONE {
+                    report_err_at(loc, RickError::NestedResult(*parent_type));
+                }
+                *parent_type |= RESULT;
+
+                self.expect(Token::Rpar);
+
+                ASTNode::Ok {
+                    value: Box::new(output),
+                }
+            },
+            Token::Err => {
+                let mut msg_type: u32 = NONE;
+
+                self.next_token();
+                self.expect(Token::Lpar);
+
+                let loc: (u32, u32) = self.current().1;
+                let output: ASTNode = self.parse_expr(&mut msg_type);
+
+                if msg_type != STRING {
+                    report_err_at(loc, RickError::TypeMismatch(STRING, msg_type));
+                }
+                *parent_type = RESULT;
+
+                self.expect(Token::Rpar);
+
+                ASTNode::Err {
+                    message: Box::new(output),
+                }
+            },
+            Token::If => {
+                self.parse_conditional(parent_type)
             },
             Token::True => {
                 self.next_token();
                 *parent_type = BOOLEAN;
 
-                return ASTNode::Value {
+                ASTNode::Value {
                     val: Value::Boolean(true),
-                };
+                }
             },
             Token::False => {
                 self.next_token();
                 *parent_type = BOOLEAN;
 
-                return ASTNode::Value {
+                ASTNode::Value {
                     val: Value::Boolean(false),
-                };
+                }
             },
 
             _ => {
@@ -716,85 +1269,208 @@ impl Parser {
         }
     }
 
+    // if c then a else b  Either branch may be 'none' or 'err(...)' as long as
+    // the other one settles the type.
+    fn parse_conditional(&mut self, parent_type: &mut u32) -> ASTNode {
+        let mut cond_type: u32 = NONE;
+        let mut then_type: u32 = NONE;
+        let mut else_type: u32 = NONE;
 
-    fn parse_arglist(&mut self, props: Properties, id: String) -> Vec<Box<ASTNode>> {
-        let mut output: Vec<Box<ASTNode>> = vec![];
+        self.expect(Token::If);
+
+        let mut loc: (u32, u32) = self.current().1;
+        let condition: ASTNode = self.parse_expr(&mut cond_type);
+
+        if cond_type != BOOLEAN {
+            report_err_at(loc, RickError::TypeMismatch(BOOLEAN, cond_type));
+        }
+
+        self.expect(Token::Then);
+        let then_value: ASTNode = self.parse_expr(&mut then_type);
+
+        self.expect(Token::Else);
+        loc = self.current().1;
+        let else_value: ASTNode = self.parse_expr(&mut else_type);
+
+        if is_assignable(then_type, else_type) {
+            *parent_type = then_type;
+        } else if is_assignable(else_type, then_type) {
+            *parent_type = else_type;
+        } else {
+            report_err_at(loc, RickError::BranchMismatch(then_type, else_type));
+        }
+
+        ASTNode::Conditional {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_value: Box::new(else_value),
+        }
+    }
+
+    // Arguments are matched by position first, then by name (name: value).
+    // Whatever is left over falls back to the parameter's default value.
+    // Trailing arguments of a variadic function come after the fixed ones,
+    // the walker collects them into the final array parameter.
+    // Numeric builtin parameters take integers or floats. A numeric return
+    // type is a float if any of them was one and an integer otherwise.
+    #[allow(clippy::vec_box, reason = "the AST keeps its children as Vec<Box<ASTNode>>")]
+    fn parse_arglist(&mut self, props: Properties, id: String, ret_type: &mut u32) -> Vec<Box<ASTNode>> {
+        let mut fixed: usize = props.params.len();
+        let mut numeric: u32 = INTEGER;
+        let mut extra: Vec<Box<ASTNode>> = vec![];
         let mut i: usize = 0;
+        let mut named: bool = false;
         self.expect(Token::Lpar);
 
-        if self.current().0.start_expression() {
-            let mut expr_type: u8 = NONE;
-            
-            output.push(Box::new(self.parse_expr(&mut expr_type)));
+        if props.variadic {
+            fixed -= 1;
+        }
+        let mut slots: Vec<Option<Box<ASTNode>>> = vec![None; fixed];
 
-            if i + 1 > props.params.len() {
-                // TODO: proper error reporting
-                panic!("Too many arguments for {}", id);
-            }
-            if expr_type != props.params[i].1 {
-                // TODO: proper error reporting
-                panic!("Type mismatch for argument {} of {}", i, id);
-            }
+        while self.current().0 != Token::Rpar {
+            let mut expr_type: u32 = NONE;
+            let mut loc: (u32, u32) = self.current().1;
+            let slot: usize;
+
+            if let (Token::Identifier(param), Token::Colon) = (self.current().0, self.peek()) {
+                slot = match props.params[..fixed].iter().position(|(name, _)| *name == param) {
+                    Some(slot) => slot,
+                    None => report_err_at(loc, RickError::UnknownParameter(id, param)),
+                };
+
+                if slots[slot].is_some() {
+                    report_err_at(loc, RickError::DuplicateArgument(id, param));
+                }
 
-            i += 1;
-            
-            while self.current().0 == Token::Comma {
-                let mut expr_type: u8 = NONE;
                 self.next_token();
-                output.push(Box::new(self.parse_expr(&mut expr_type)));
-                if i + 1 > props.params.len() {
-                    // TODO: proper error reporting
-                    panic!("Too many arguments for {}", id);
+                self.next_token();
+                loc = self.current().1;
+                named = true;
+            } else {
+                if named {
+                    report_err_at(loc, RickError::PositionalAfterNamed(id));
                 }
-                if expr_type != props.params[i].1 {
-                    // TODO: proper error reporting
-                    panic!("Type mismatch for argument {} of {}", i, id);
+                if i >= fixed && !props.variadic {
+                    report_err_at(loc, RickError::TooManyArguments(id));
                 }
 
+                slot = i;
                 i += 1;
             }
-        }
 
-        if i != props.params.len() {
-            panic!("Too few arguments to {}", id);
+            let expr: ASTNode = self.parse_expr(&mut expr_type);
+            let (param, mut tipe) = props.params[slot.min(fixed)].clone();
+
+            if slot >= fixed {
+                tipe &= !ARRAY;
+            }
+            if tipe == NUMERIC && is_numeric_type(expr_type) {
+                tipe = expr_type;
+                numeric |= expr_type;
+            }
+            if tipe == ANY && expr_type != NONE {
+                tipe = expr_type;
+            }
+            if tipe == SIZED && (is_array_type(expr_type) || expr_type == STRING) {
+                tipe = expr_type;
+            }
+            if !is_assignable(tipe, expr_type) {
+                report_err_at(loc, RickError::ArgumentType(id, param, tipe, expr_type));
+            }
+
+            if slot < fixed {
+                slots[slot] = Some(Box::new(expr));
+            } else {
+                extra.push(Box::new(expr));
+            }
+
+            if self.current().0 != Token::Comma {
+                break;
+            }
+            self.next_token();
         }
 
+        let loc: (u32, u32) = self.current().1;
         self.expect(Token::Rpar);
 
+        let mut output: Vec<Box<ASTNode>> = vec![];
+
+        for (slot, ((param, _), default)) in slots.into_iter().zip(props.params.iter().zip(&props.defaults)) {
+            match (slot, default) {
+                (Some(arg), _) => output.push(arg),
+                (None, Some(val)) => output.push(Box::new(ASTNode::Value { val: val.clone() })),
+                (None, None) => report_err_at(loc, RickError::MissingArgument(id, param.clone())),
+            }
+        }
+
+        output.append(&mut extra);
+
+        if *ret_type & NUMERIC == NUMERIC {
+            let resolved: u32 = if numeric & FLOAT != 0 { FLOAT } else { INTEGER };
+            *ret_type = (*ret_type & !NUMERIC) | resolved;
+        }
+
         output
     }
 
-
 // #######################################################################
 // ####################### UTILITY FUNCTIONS #############################
 // #######################################################################
-    fn parse_func_type_info(&mut self) {  
+    fn parse_func_type_info(&mut self, public: bool) {  
         let mut name: String = String::new();
-        let mut args: Vec<(String, u8)> = vec![];
-        let mut ret_type: u8 = NONE;
+        let mut args: Vec<(String, u32)> = vec![];
+        let mut defaults: Vec<Option<Value>> = vec![];
+        let mut param_locs: Vec<(u32, u32)> = vec![];
+        let mut variadic: bool = false;
+        let mut ret_type: u32 = NONE;
 
         self.next_token();
 
+        let loc: (u32, u32) = self.current().1;
         self.expect_identifier(&mut name);
 
-        self.expect(Token::Lpar);
+        if builtins::lookup(&name).is_some() {
+            report_err_at(loc, RickError::BuiltinRedefinition(name));
+        }
+        name = self.qualify(&name);
 
-        if self.current().0.is_type_start() {
-            let mut t: u8 = self.parse_type();
-            
-            let mut id: String = String::new();
-            self.expect_identifier(&mut id);
+        if self.symboltable.contains_key(&name) {
+            report_err_at(loc, RickError::Redefinition(name));
+        }
 
-            args.push((id, t));
+        self.expect(Token::Lpar);
 
-            while self.current().0 == Token::Comma {
+        if self.current().0.is_type_start() {
+            loop {
                 let mut id: String = String::new();
-                self.next_token();
+                let t: u32 = self.parse_type();
+
+                // string ... parts  has to come last and arrives as a string array
+                if self.current().0 == Token::Ellipsis {
+                    if t & ARRAY != 0 {
+                        report_err_at(self.current().1, RickError::NestedArray(t));
+                    }
+                    self.next_token();
+                    param_locs.push(self.current().1);
+                    self.expect_identifier(&mut id);
+
+                    variadic = true;
+                    defaults.push(None);
+                    args.push((id, t | ARRAY));
+                    break;
+                }
 
-                t = self.parse_type();
+                param_locs.push(self.current().1);
                 self.expect_identifier(&mut id);
 
+                let required: bool = defaults.last().is_some_and(|d: &Option<Value>| d.is_some());
+                defaults.push(self.parse_param_default(&id, t, required));
                 args.push((id, t));
+
+                if self.current().0 != Token::Comma {
+                    break;
+                }
+                self.next_token();
             }
         }
         
@@ -809,13 +1485,112 @@ impl Parser {
             tipe: ret_type,
             offset: None,
             params: args,
+            defaults,
+            param_locs,
+            variadic,
+            public,
         };
 
+        if name == "main" {
+            self.check_main(&props, loc);
+        }
+
         self.symboltable.insert(name, props);
     }
 
-    fn parse_type(&mut self) -> u8 {
-        let mut output: u8 = NONE;
+    // main takes the program arguments, only the arguments or nothing at
+    // all. A procedure main exits with 0.
+    fn check_main(&self, props: &Properties, loc: (u32, u32)) {
+        let params: Vec<u32> = props.params.iter().map(|(_, t)| *t).collect();
+
+        let params_ok: bool = match params[..] {
+            [] => true,
+            [args] => args == STRING | ARRAY,
+            [argc, args] => argc == INTEGER && args == STRING | ARRAY,
+            _ => false,
+        };
+        if !params_ok || props.variadic || props.defaults.iter().any(|d| d.is_some()) {
+            report_err_at(loc, RickError::MainParameters);
+        }
+
+        let ret: u32 = props.tipe & !FUNC;
+        if ret != NONE && ret != INTEGER && ret != RESULT | INTEGER {
+            report_err_at(loc, RickError::MainReturnType(ret));
+        }
+    }
+
+    // string live = "*"  Once a parameter has a default, the rest need one too.
+    fn parse_param_default(&mut self, param: &str, tipe: u32, required: bool) -> Option<Value> {
+        if self.current().0 != Token::Assign {
+            if required {
+                report_err_at(self.current().1, RickError::RequiredAfterDefault(param.to_string()));
+            }
+
+            return None;
+        }
+
+        self.next_token();
+
+        let loc: (u32, u32) = self.current().1;
+        let mut value_type: u32 = NONE;
+        let value: Value = self.parse_literal(&mut value_type);
+
+        if !is_assignable(tipe, value_type) {
+            report_err_at(loc, RickError::TypeMismatch(tipe, value_type));
+        }
+
+        Some(value)
+    }
+
+    fn parse_literal(&mut self, tipe: &mut u32) -> Value {
+        let output: Value;
+        let negative: bool = self.current().0 == Token::Sub;
+
+        if negative {
+            self.next_token();
+        }
+
+        match self.current().0 {
+            Token::IntegerLiteral(i) => {
+                *tipe = INTEGER;
+                output = Value::Integer(if negative { -i } else { i });
+            },
+            Token::FloatLiteral(f) => {
+                *tipe = FLOAT;
+                output = Value::Float(if negative { -f } else { f });
+            },
+            Token::StringLiteral(s) if !negative => {
+                *tipe = STRING;
+                output = Value::String(s);
+            },
+            Token::CharLiteral(c) if !negative => {
+                *tipe = CHAR;
+                output = Value::Char(c);
+            },
+            Token::True if !negative => {
+                *tipe = BOOLEAN;
+                output = Value::Boolean(true);
+            },
+            Token::False if !negative => {
+                *tipe = BOOLEAN;
+                output = Value::Boolean(false);
+            },
+            Token::None if !negative => {
+                *tipe = OPTIONAL;
+                output = Value::Optional(None);
+            },
+            found => {
+                report_err_at(self.current().1, RickError::ExpectedLiteral(found));
+            },
+        }
+
+        self.next_token();
+
+        output
+    }
+
+    fn parse_type(&mut self) -> u32 {
+        let mut output: u32 = NONE;
         
         if !self.current().0.is_type_start() {
             let (line, col) = self.current().1;
@@ -823,6 +1598,16 @@ impl Parser {
             report_err(RickError::MissingTypeSpecifier);
         }
 
+        if self.current().0 == Token::Result {
+            output |= RESULT;
+            self.next_token();
+        }
+
+        if self.current().0 == Token::Optional {
+            output |= OPTIONAL;
+            self.next_token();
+        }
+
         match self.current().0 {
             Token::Integer => {
                 output |= INTEGER;
@@ -835,6 +1620,32 @@ impl Parser {
             },
             Token::String => {
                 output |= STRING;
+            },
+            Token::Char => {
+                output |= CHAR;
+            },
+            Token::File => {
+                output |= FILE;
+            },
+            Token::Lpar => {
+                let mut elements: Vec<u32> = vec![];
+
+                self.next_token();
+                elements.push(self.parse_type());
+
+                while self.current().0 == Token::Comma {
+                    self.next_token();
+                    elements.push(self.parse_type());
+                }
+
+                if elements.len() < 2 {
+                    report_err_at(self.current().1, RickError::Expected(self.current().0, Token::Comma));
+                }
+                if self.current().0 != Token::Rpar {
+                    report_err_at(self.current().1, RickError::Expected(self.current().0, Token::Rpar));
+                }
+
+                output |= tuple_type(elements);
             }
             _ => {
                 panic!("Unreachable!");
@@ -851,6 +1662,93 @@ impl Parser {
         output
     }
 
+    // Left hand side of a bitwise operator, the operator is the current token.
+    fn expect_integer_operand(&self, tipe: u32) {
+        if tipe != INTEGER {
+            report_err_at(self.current().1, RickError::TypeMismatch(INTEGER, tipe));
+        }
+    }
+
+    // '?' returns the error from functions that give back a result. In main it
+    // ends the program instead, anywhere else it can't be used.
+    fn try_propagates(&self, loc: (u32, u32)) -> bool {
+        if self.current_ret_type & RESULT != 0 {
+            true
+        } else if self.current_func == "main" || self.in_test {
+            false
+        } else {
+            report_err_at(loc, RickError::TryOutsideResult(self.current_func.clone()));
+        }
+    }
+
+    // Token straight after the argument list starting at the current '('.
+    fn peek_past_arglist(&self) -> Token {
+        let mut depth: u32 = 0;
+        let mut i: usize = self.idx;
+
+        while i < self.tokens.len() - 1 {
+            match self.tokens[i].0 {
+                Token::Lpar => depth += 1,
+                Token::Rpar => depth -= 1,
+                _ => {},
+            }
+            i += 1;
+
+            if depth == 0 {
+                break;
+            }
+        }
+
+        self.tokens[i].0.clone()
+    }
+
+    // Functions in imported modules live in the symbol table as 'module::name'.
+    fn qualify(&self, name: &str) -> String {
+        let module: &String = &self.modules[self.module].name;
+
+        if module.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}::{}", module, name)
+        }
+    }
+
+    // Reads 'name' or 'module::name' and resolves it against the current module.
+    fn expect_function_name(&mut self) -> (String, Properties) {
+        let loc: (u32, u32) = self.current().1;
+        let mut id: String = String::new();
+        self.expect_identifier(&mut id);
+
+        if self.current().0 != Token::DoubleColon {
+            // builtins are visible from every module
+            let name: String = match builtins::lookup(&id) {
+                Some(_) => id.clone(),
+                None => self.qualify(&id),
+            };
+
+            return match self.symboltable.get(&name) {
+                Some(props) => (name, props.clone()),
+                None => report_err_at(loc, RickError::UndefinedFunction(id)),
+            };
+        }
+
+        self.next_token();
+        let mut func: String = String::new();
+        self.expect_identifier(&mut func);
+
+        let current: &Module = &self.modules[self.module];
+        if id != current.name && !current.imports.contains(&id) {
+            report_err_at(loc, RickError::UnknownModule(id));
+        }
+
+        let name: String = format!("{}::{}", id, func);
+        match self.symboltable.get(&name) {
+            Some(props) if props.public || id == current.name => (name, props.clone()),
+            Some(_) => report_err_at(loc, RickError::PrivateFunction(name)),
+            None => report_err_at(loc, RickError::UndefinedFunction(name)),
+        }
+    }
+
     fn expect(&mut self, expected: Token) {
         if std::mem::discriminant(&expected) == std::mem::discriminant(&self.current().0) {
             self.next_token();
@@ -878,8 +1776,33 @@ impl Parser {
         }
     }
 
+    fn peek(&self) -> Token {
+        self.tokens[(self.idx + 1).min(self.tokens.len() - 1)].0.clone()
+    }
+
     #[inline(always)]
     fn current(&self) -> (Token, (u32, u32)) {
         self.tokens[self.idx].clone()
     }
-}
\ No newline at end of file
+}
+
+// Parses a whole program for the unit tests of the passes that run on the
+// AST. The scanner keeps its position in globals, so one file at a time.
+#[cfg(test)]
+pub fn parse_text(text: &str) -> ASTNode {
+    use std::sync::Mutex;
+    use crate::module::Loader;
+
+    static PARSING: Mutex<usize> = Mutex::new(0);
+
+    let mut count = PARSING.lock().unwrap_or_else(|e| e.into_inner());
+    *count += 1;
+
+    let path = std::env::temp_dir().join(format!("rick-test-{}-{}.ric", std::process::id(), count));
+    std::fs::write(&path, text).unwrap();
+
+    let modules: Vec<Module> = Loader::new().load_program(path.display().to_string());
+    std::fs::remove_file(&path).unwrap();
+
+    Parser::new(modules).parse_tok_stream(false)
+}
@@ -1,12 +1,36 @@
 use std::fmt;
+use std::sync::Mutex;
 
-pub const NONE: u8 = 0;
-pub const BOOLEAN: u8 = 1;
-pub const INTEGER: u8 = 2;
-pub const FLOAT: u8 = 4;
-pub const STRING: u8 = 8;
-pub const ARRAY: u8 = 16;
-pub const FUNC: u8 = 32;
+use crate::value::Value;
+
+pub const NONE: u32 = 0;
+pub const BOOLEAN: u32 = 1;
+pub const INTEGER: u32 = 2;
+pub const FLOAT: u32 = 4;
+pub const STRING: u32 = 8;
+pub const ARRAY: u32 = 16;
+pub const FUNC: u32 = 32;
+pub const TUPLE: u32 = 64;
+pub const OPTIONAL: u32 = 128;
+pub const RESULT: u32 = 256;
+pub const CHAR: u32 = 512;
+pub const FILE: u32 = 1024;
+
+// Only used by builtins: a parameter that takes an integer or a float. As a
+// return type it stands for whichever of the two the arguments were.
+pub const NUMERIC: u32 = INTEGER | FLOAT;
+
+// Also only for builtins: a parameter that takes a value of any type.
+pub const ANY: u32 = 2048;
+
+// And one that takes an array of any type or a string.
+pub const SIZED: u32 = 4096;
+
+const TUPLE_SHIFT: u32 = 16;
+static TUPLE_TYPES: Mutex<Vec<Vec<u32>>> = Mutex::new(Vec::new());
+
+// (integer, integer), interned first so builtins can use it as a constant
+pub const INTEGER_PAIR: u32 = TUPLE;
 
 /*
     0 = NONE,
@@ -16,117 +40,176 @@ pub const FUNC: u8 = 32;
     8 = STRING,
     16 = ARRAY,
     32 = FUNC, 
+    64 = TUPLE, element types live in TUPLE_TYPES
+    128 = OPTIONAL, on its own it is the type of 'none'
+    256 = RESULT, on its own it is the type of 'err(...)'
+    512 = CHAR,
+    1024 = FILE, a handle from open()
 */
 
 // XXX: this looks useful
 // type tipe = [bool; 7];
 #[derive(Debug, Clone)]
 pub struct Properties {
-    pub tipe: u8,
+    pub tipe: u32,
     pub offset: Option<u32>,
 
-    pub params: Vec<(String, u8)>,
+    pub params: Vec<(String, u32)>,
+
+    // default value for each of params, if it has one
+    pub defaults: Vec<Option<Value>>,
+
+    // where each of params is declared, builtins have none
+    pub param_locs: Vec<(u32, u32)>,
+
+    // the last parameter is an array collecting any trailing arguments
+    pub variadic: bool,
+
+    // only public functions can be called from other modules
+    pub public: bool,
 }
 
-pub fn type_string(t: u8) -> String {
+impl Properties {
+    pub fn variable(tipe: u32, offset: u32) -> Properties {
+        Properties {
+            tipe,
+            offset: Some(offset),
+            params: vec![],
+            defaults: vec![],
+            param_locs: vec![],
+            variadic: false,
+            public: false,
+        }
+    }
+}
+
+pub fn type_string(t: u32) -> String {
     let mut out: String = String::new();
 
     if t & FUNC != 0 {
         // callable
-        if t & NONE != 0 {
+        if t & !FUNC == NONE {
             out.push_str("procedure");
         } else {
-            if t & BOOLEAN != 0 {
-                out.push_str("boolean ");
-            } else if t & INTEGER != 0 {
-                out.push_str("integer ");
-            } else if t & FLOAT != 0 {
-                out.push_str("float ");
-            } else if t & STRING != 0 {
-                out.push_str("string ");
-            }
-
-            if t & ARRAY != 0 {
-                out.push_str("array ");
-            }
-
-            out.push_str("function");
+            out.push_str(&type_string(t & !FUNC));
+            out.push_str(" function");
         }
 
-    } else {
-        if t & BOOLEAN != 0 {
-            out.push_str("boolean");
-        } else if t & INTEGER != 0 {
-            out.push_str("integer");
-        } else if t & FLOAT != 0 {
-            out.push_str("float");
-        } else if t & STRING != 0 {
-            out.push_str("string");
-        }
+        return out;
+    }
 
-        if t & ARRAY != 0 {
-            out.push_str("array");
-        }
+    if t == RESULT {
+        return "result".into();
+    } else if t & RESULT != 0 {
+        return format!("result {}", type_string(t & !RESULT));
+    }
+
+    if t == OPTIONAL {
+        return "none".into();
+    } else if t & OPTIONAL != 0 {
+        out.push_str("optional ");
+    }
+
+    if t == ANY {
+        return "any".into();
+    } else if t == SIZED {
+        return "array or string".into();
+    }
+
+    if t & NUMERIC == NUMERIC {
+        out.push_str("integer or float");
+    } else if t & BOOLEAN != 0 {
+        out.push_str("boolean");
+    } else if t & INTEGER != 0 {
+        out.push_str("integer");
+    } else if t & FLOAT != 0 {
+        out.push_str("float");
+    } else if t & STRING != 0 {
+        out.push_str("string");
+    } else if t & CHAR != 0 {
+        out.push_str("char");
+    } else if t & FILE != 0 {
+        out.push_str("file");
+    } else if t & TUPLE != 0 {
+        let elements: Vec<String> = tuple_elements(t).iter().map(|e| type_string(*e)).collect();
+        out.push_str(&format!("({})", elements.join(", ")));
+    }
+
+    if t & ARRAY != 0 {
+        out.push_str(" array");
     }
 
     out
 }
 
-pub fn is_numeric_type(t: u8) -> bool {
-    if t == INTEGER || t == FLOAT {
+// Tuple types don't fit in the flags, so their element types are interned
+// and the index is stored above the flag bits. Interning keeps '==' usable
+// for comparing types.
+pub fn tuple_type(elements: Vec<u32>) -> u32 {
+    let mut types = tuple_types();
+
+    let id: usize = match types.iter().position(|t| *t == elements) {
+        Some(id) => id,
+        None => {
+            types.push(elements);
+            types.len() - 1
+        },
+    };
+
+    TUPLE | ((id as u32) << TUPLE_SHIFT)
+}
+
+pub fn tuple_elements(t: u32) -> Vec<u32> {
+    tuple_types()[(t >> TUPLE_SHIFT) as usize].clone()
+}
+
+fn tuple_types() -> std::sync::MutexGuard<'static, Vec<Vec<u32>>> {
+    let mut types = TUPLE_TYPES.lock().unwrap();
+
+    if types.is_empty() {
+        types.push(vec![INTEGER, INTEGER]);
+    }
+
+    types
+}
+
+// 'none' fits any optional type and 'err(...)' any result type,
+// everything else has to match exactly.
+pub fn is_assignable(target: u32, value: u32) -> bool {
+    if target == value {
         true
+    } else if value == RESULT {
+        target & RESULT != 0
+    } else if target & RESULT != 0 && value & RESULT != 0 {
+        is_assignable(target & !RESULT, value & !RESULT)
     } else {
-        false
+        value == OPTIONAL && target & (OPTIONAL | RESULT) == OPTIONAL
     }
 }
 
+// Optional and result arrays have to be unwrapped before they can be indexed.
+pub fn is_array_type(t: u32) -> bool {
+    t & (ARRAY | OPTIONAL | RESULT) == ARRAY
+}
+
+// Types that can be compared with < and friends.
+pub fn is_ordered_type(t: u32) -> bool {
+    is_numeric_type(t) || t == CHAR
+}
+
+pub fn is_numeric_type(t: u32) -> bool {
+    t == INTEGER || t == FLOAT
+}
+
 impl fmt::Display for Properties {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), std::fmt::Error> {
 
-        if self.offset.is_some() {
-            write!(f, "@{} ", self.offset.unwrap())?;
+        if let Some(offset) = self.offset {
+            write!(f, "@{} ", offset)?;
         } else {
             write!(f, "@_ ")?;
         }
 
-        if self.tipe & FUNC != 0 {
-            // callable
-            if self.tipe & NONE != 0 {
-                write!(f, "procedure")
-            } else {
-                if self.tipe & BOOLEAN != 0 {
-                    write!(f, "boolean ")?;
-                } else if self.tipe & INTEGER != 0 {
-                    write!(f, "integer ")?;
-                } else if self.tipe & FLOAT != 0 {
-                    write!(f, "float ")?;
-                } else if self.tipe & STRING != 0 {
-                    write!(f, "string ")?;
-                }
-
-                if self.tipe & ARRAY != 0 {
-                    write!(f, "array ")?;
-                }
-
-                write!(f, "function")
-            }
-
-        } else {
-            if self.tipe & BOOLEAN != 0 {
-                write!(f, "boolean")?;
-            } else if self.tipe & INTEGER != 0 {
-                write!(f, "integer")?;
-            } else if self.tipe & FLOAT != 0 {
-                write!(f, "float")?;
-            } else if self.tipe & STRING != 0 {
-                write!(f, "string")?;
-            }
-
-            if self.tipe & ARRAY != 0 {
-                write!(f, " array")?;
-            }
-
-            Ok(())
-        }
+        write!(f, "{}", type_string(self.tipe))
     }
-}
\ No newline at end of file
+}
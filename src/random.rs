@@ -0,0 +1,119 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// xorshift64*, small and good enough for simulations. Not for anything
+// that needs to be unpredictable.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    // The seed is spread out with splitmix64 first, so small seeds like 1
+    // and 2 still give unrelated sequences and the state is never zero.
+    pub fn new(seed: u64) -> Rng {
+        let mut z: u64 = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+
+        Rng {
+            state: if z == 0 { 0x9E3779B97F4A7C15 } else { z },
+        }
+    }
+
+    // A different seed every run, for when no --seed is given.
+    pub fn clock_seed() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // Anywhere from lo to hi, both included. Values from the uneven tail of
+    // the u64 range are thrown away so every outcome is equally likely.
+    pub fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        let span: u64 = (hi as i64 - lo as i64 + 1) as u64;
+        let zone: u64 = u64::MAX - u64::MAX % span;
+
+        loop {
+            let x: u64 = self.next_u64();
+
+            if x < zone {
+                return (lo as i64 + (x % span) as i64) as i32;
+            }
+        }
+    }
+
+    // In [0, 1), the top 24 bits are all an f32 can hold.
+    pub fn float(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a: Rng = Rng::new(7);
+        let mut b: Rng = Rng::new(7);
+
+        assert_eq!(a.next_u64(), 0x14EAA7D1F828843A);
+        assert_eq!(a.next_u64(), 0x421D9D8FFF2D1844);
+        assert_eq!(a.next_u64(), 0x5AA548BBD8C601D5);
+
+        for _ in 0..3 {
+            b.next_u64();
+        }
+        for _ in 0..100 {
+            assert_eq!(a.range(-5, 5), b.range(-5, 5));
+            assert_eq!(a.float(), b.float());
+        }
+    }
+
+    #[test]
+    fn small_seeds_are_spread_out() {
+        assert_eq!(Rng::new(0).state, 0xE220A8397B1DCDAF);
+        assert_eq!(Rng::new(0).next_u64(), 0x7BBCB40D550682D0);
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn range_stays_in_bounds() {
+        let mut rng: Rng = Rng::new(1);
+
+        for _ in 0..1000 {
+            let x: i32 = rng.range(-3, 4);
+            assert!((-3..=4).contains(&x));
+        }
+        assert_eq!(rng.range(9, 9), 9);
+
+        let full: i32 = rng.range(i32::MIN, i32::MAX);
+        assert!((i32::MIN..=i32::MAX).contains(&full));
+    }
+
+    #[test]
+    fn range_throws_away_the_uneven_tail() {
+        // The next output from this state is u64::MAX, the only value a span
+        // of 3 rejects. Taken as is it would give 0, the one after gives 2.
+        let mut rng: Rng = Rng { state: 0xA8D395BE4B19CCE8 };
+
+        assert_eq!(rng.range(0, 2), 2);
+    }
+
+    #[test]
+    fn float_is_below_one() {
+        let mut rng: Rng = Rng::new(3);
+
+        for _ in 0..1000 {
+            let x: f32 = rng.float();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
@@ -33,14 +33,27 @@ impl Scanner {
             ("true".into(), Token::True),
             ("let".into(), Token::Let),
             ("if".into(), Token::If),
+            ("then".into(), Token::Then),
             ("while".into(), Token::While),
             ("return".into(), Token::Return),
             ("read".into(), Token::Read),
             ("print".into(), Token::Print),
+            ("eprint".into(), Token::Eprint),
             ("integer".into(), Token::Integer),
             ("float".into(), Token::Float),
             ("string".into(), Token::String),
+            ("char".into(), Token::Char),
+            ("file".into(), Token::File),
             ("var".into(), Token::Var),
+            ("import".into(), Token::Import),
+            ("pub".into(), Token::Pub),
+            ("optional".into(), Token::Optional),
+            ("some".into(), Token::Some),
+            ("none".into(), Token::None),
+            ("result".into(), Token::Result),
+            ("ok".into(), Token::Ok),
+            ("err".into(), Token::Err),
+            ("assert".into(), Token::Assert),
         ]);
 
         Scanner {
@@ -78,12 +91,15 @@ impl Scanner {
         if matches!(self.ch(), 'A'..='Z' | 'a'..='z' | '_') {
             return (self.scan_word(),  get_loc());
 
-        } else if matches!(self.ch(), '0'..='9') {
+        } else if self.ch().is_ascii_digit() {
             return (self.scan_number(), get_loc());
 
         } else if self.ch() == '"' {
             return (self.scan_string(), get_loc());
 
+        } else if self.ch() == '\'' {
+            return (self.scan_char(), get_loc());
+
         } else {
             match self.ch() {
                 '(' => {
@@ -118,8 +134,30 @@ impl Scanner {
                     output = Token::Semicolon;
                 },
 
+                '?' => {
+                    output = Token::Question;
+                },
+
+                '.' => {
+                    for _ in 0..2 {
+                        self.next_char();
+
+                        if self.is_eof() || self.ch() != '.' {
+                            report_err(RickError::IllegalCharacter(b'.'));
+                        }
+                    }
+
+                    output = Token::Ellipsis;
+                },
+
                 ':' => {
-                    output = Token::Colon;
+                    self.next_char();
+
+                    if !self.is_eof() && self.ch() == ':' {
+                        output = Token::DoubleColon;
+                    } else {
+                        return (Token::Colon, get_loc());
+                    }
                 },
 
                 '!' => {
@@ -140,6 +178,8 @@ impl Scanner {
                         output = Token::ArrowLeft;
                     } else if self.ch() == '>' {
                         output = Token::Concat;
+                    } else if self.ch() == '<' {
+                        output = Token::Shl;
                     } else {
                         return (Token::Lt, get_loc());
                     }
@@ -192,11 +232,35 @@ impl Scanner {
 
                     if self.ch() == '=' {
                         output = Token::Gte;
+                    } else if self.ch() == '>' {
+                        self.next_char();
+
+                        if self.ch() == '>' {
+                            output = Token::Ushr;
+                        } else {
+                            return (Token::Shr, get_loc());
+                        }
                     } else {
                         return (Token::Gt, get_loc());
                     }
                 },
 
+                '&' => {
+                    output = Token::BitAnd;
+                },
+
+                '|' => {
+                    output = Token::BitOr;
+                },
+
+                '^' => {
+                    output = Token::BitXor;
+                },
+
+                '~' => {
+                    output = Token::BitNot;
+                },
+
                 _ => {
                     report_err(RickError::IllegalCharacter(self.ch() as u8));
                 },
@@ -216,20 +280,7 @@ impl Scanner {
 
         while !self.is_eof() && self.ch() != '"' {
             if self.ch() == '\\' {
-                self.next_char();
-
-                if !matches!(self.ch(), '\\' | 't' | 'n' | '"') {
-                    set_col(*COLUMN_NUM.lock().unwrap());
-                    report_err(RickError::IllegalEscapeCode(self.ch()));
-                } else {
-                    match self.ch() {
-                        'n' => string.push('\n'),
-                        '\\' => string.push('\\'),
-                        't' => string.push('\t'),
-                        '"' => string.push('"'),
-                        _ => panic!("Unreachable"),
-                    }
-                }
+                string.push(self.scan_escape());
             } else {
                 string.push(self.ch());
             }
@@ -246,6 +297,46 @@ impl Scanner {
         Token::StringLiteral(string)
     }
 
+    fn scan_char(&mut self) -> Token {
+        let ch: char;
+
+        self.next_char();
+
+        if self.is_eof() || self.ch() == '\'' {
+            report_err(RickError::MalformedChar);
+        } else if self.ch() == '\\' {
+            ch = self.scan_escape();
+        } else {
+            ch = self.ch();
+        }
+
+        self.next_char();
+
+        if self.is_eof() || self.ch() != '\'' {
+            report_err(RickError::MalformedChar);
+        }
+        self.next_char();
+
+        Token::CharLiteral(ch)
+    }
+
+    // Shared by string and char literals, the current character is the backslash.
+    fn scan_escape(&mut self) -> char {
+        self.next_char();
+
+        match self.ch() {
+            'n' => '\n',
+            '\\' => '\\',
+            't' => '\t',
+            '"' => '"',
+            '\'' => '\'',
+            _ => {
+                set_col(*COLUMN_NUM.lock().unwrap());
+                report_err(RickError::IllegalEscapeCode(self.ch()));
+            },
+        }
+    }
+
     fn scan_number(&mut self) -> Token {
         let mut digits: String = String::new();
         let mut is_float: bool = false;
@@ -267,18 +358,14 @@ impl Scanner {
         }
 
         if is_float {
-            let res = digits.parse::<f32>();
-            if res.is_ok() {
-                return Token::FloatLiteral(res.unwrap());
-            } else {
-                report_err(RickError::NumberParseFailure);
+            match digits.parse::<f32>() {
+                Ok(f) => Token::FloatLiteral(f),
+                Err(_) => report_err(RickError::NumberParseFailure),
             }
         } else {
-            let res = digits.parse::<i32>();
-            if res.is_ok() {
-                return Token::IntegerLiteral(res.unwrap());
-            } else {
-                report_err(RickError::NumberParseFailure);
+            match digits.parse::<i32>() {
+                Ok(i) => Token::IntegerLiteral(i),
+                Err(_) => report_err(RickError::NumberParseFailure),
             }
         }
     }
@@ -291,12 +378,9 @@ impl Scanner {
             self.next_char();            
         }
 
-        let res = self.reserved_words.get(&word);
-
-        if res.is_some() {
-            res.unwrap().clone()
-        } else {
-            Token::Identifier(word)
+        match self.reserved_words.get(&word) {
+            Some(token) => token.clone(),
+            None => Token::Identifier(word),
         }
     }
 
@@ -331,8 +415,4 @@ impl Scanner {
     fn is_eof(&self) -> bool {
         self.idx >= self.source.len()
     }
-}
-
-fn is_printable(ch: char) -> bool {
-    matches!(ch as u8, 0x20..=0x7e)
 }
\ No newline at end of file
@@ -11,6 +11,7 @@ pub enum Token {
     StringLiteral(String),  // literal string e.g. "hello world"
     IntegerLiteral(i32),    // literal int e.g. 42                            
     FloatLiteral(f32),      // literal float e.g. 3.1415
+    CharLiteral(char),      // literal char e.g. 'a'
     
     // Literals end
 
@@ -23,18 +24,31 @@ pub enum Token {
     False,          // "false"
     Let,            // "let"
     If,             // "if"
+    Then,           // "then" e.g. let x = if a > b then a else b;
     Elif,           // "elif"
     Else,           // "else"
     While,          // "while"
     Return,         // "return"
     Read,           // "read"
     Print,          // "print"
+    Eprint,         // "eprint"
     Array,          // "array"
     Integer,        // "int"
     Float,          // "float"
     Boolean,        // "bool"
     String,         // "string"
+    Char,           // "char"
+    File,           // "file"
     Var,            // "var"
+    Import,         // "import"
+    Pub,            // "pub"
+    Optional,       // "optional"
+    Some,           // "some"
+    None,           // "none"
+    Result,         // "result"
+    Ok,             // "ok"
+    Err,            // "err"
+    Assert,         // "assert"
     
     // Reserved words end
 
@@ -48,7 +62,10 @@ pub enum Token {
     Comma,          // ' , '
     Semicolon,      // ' ; '
     Colon,          // ' : '
+    DoubleColon,    // ' :: ' e.g. util::print_grid(g)
     Negate,         // ' ! ' 
+    Question,       // ' ? ' e.g. let n = parse(s)?;
+    Ellipsis,       // ' ... ' e.g. func log(string ... parts)
     
     ArrowLeft,      // ' <- '
     ArrowRight,     // ' -> '
@@ -61,6 +78,14 @@ pub enum Token {
     Mod,            // ' % '
     Pow,            // ' ** '
 
+    BitAnd,         // ' & '
+    BitOr,          // ' | '
+    BitXor,         // ' ^ '
+    BitNot,         // ' ~ '
+    Shl,            // ' << '
+    Shr,            // ' >> ' keeps the sign
+    Ushr,           // ' >>> ' fills with zeroes
+
     Assign,         // ' = '
 
     Eq,             // ' == '
@@ -89,26 +114,28 @@ impl Token {
         matches!(self, Token::Mul | Token::Div | Token::And | Token::Mod)
     }
 
+    pub fn is_shift_op(&self) -> bool {
+        matches!(self, Token::Shl | Token::Shr | Token::Ushr)
+    }
+
     pub fn is_exponent_op(&self) -> bool {
         matches!(self, Token::Pow)
     }
 
     pub fn is_type_start(&self) -> bool {
-        matches!(self, Token::Integer | Token::Float | Token::Boolean | Token::String)
+        matches!(self, Token::Integer | Token::Float | Token::Boolean | Token::String | Token::Char
+                | Token::File | Token::Lpar | Token::Optional | Token::Result)
     }
 
     pub fn starts_base(&self) -> bool {
         matches!(self, Token::Identifier(_) | Token::IntegerLiteral(_) 
-                | Token::FloatLiteral(_) | Token::StringLiteral(_) | Token::True 
-                | Token::False | Token::Lpar | Token::Negate)
+                | Token::FloatLiteral(_) | Token::StringLiteral(_) | Token::CharLiteral(_) | Token::True 
+                | Token::False | Token::Lpar | Token::Negate | Token::BitNot | Token::Some
+                | Token::None | Token::Ok | Token::Err | Token::If)
     }
 
     pub fn start_expression(&self) -> bool {
-        if self.starts_base() || matches!(self, Token::Sub) {
-            true
-        } else {
-            false
-        }
+        self.starts_base() || matches!(self, Token::Sub)
     }
 }
 
@@ -121,6 +148,7 @@ impl fmt::Display for Token {
             Token::StringLiteral(s) => write!(f, "{}", s),
             Token::IntegerLiteral(n) => write!(f, "{}", n),
             Token::FloatLiteral(n) => write!(f, "{}", n),
+            Token::CharLiteral(c) => write!(f, "'{}'", c),
 
             Token::And => write!(f, "and"),
             Token::Or => write!(f, "or"),
@@ -129,18 +157,31 @@ impl fmt::Display for Token {
             Token::False => write!(f, "false"),
             Token::Let => write!(f, "let"),
             Token::If => write!(f, "if"),
+            Token::Then => write!(f, "then"),
             Token::Elif => write!(f, "elif"),
             Token::Else => write!(f, "else"),
             Token::While => write!(f, "while"),
             Token::Return => write!(f, "return"),
             Token::Read => write!(f, "read"),
             Token::Print => write!(f, "print"),
+            Token::Eprint => write!(f, "eprint"),
             Token::Array => write!(f, "array"),
             Token::Integer => write!(f, "integer"),
             Token::Float => write!(f, "float"),
             Token::Boolean => write!(f, "boolean"),
             Token::String => write!(f, "string"),
+            Token::Char => write!(f, "char"),
+            Token::File => write!(f, "file"),
             Token::Var => write!(f, "var"),
+            Token::Import => write!(f, "import"),
+            Token::Pub => write!(f, "pub"),
+            Token::Optional => write!(f, "optional"),
+            Token::Some => write!(f, "some"),
+            Token::None => write!(f, "none"),
+            Token::Result => write!(f, "result"),
+            Token::Ok => write!(f, "ok"),
+            Token::Err => write!(f, "err"),
+            Token::Assert => write!(f, "assert"),
 
             Token::Lpar => write!(f, "("),
             Token::Rpar => write!(f, ")"),
@@ -152,7 +193,10 @@ impl fmt::Display for Token {
             Token::Comma => write!(f, ","),
             Token::Semicolon => write!(f, ";"),
             Token::Colon => write!(f, ":"),
+            Token::DoubleColon => write!(f, "::"),
             Token::Negate => write!(f, "!"),
+            Token::Question => write!(f, "?"),
+            Token::Ellipsis => write!(f, "..."),
 
             Token::ArrowLeft => write!(f, "<-"),
             Token::ArrowRight => write!(f, "->"),
@@ -165,6 +209,14 @@ impl fmt::Display for Token {
             Token::Mod => write!(f, "%"),
             Token::Pow => write!(f, "**"),
 
+            Token::BitAnd => write!(f, "&"),
+            Token::BitOr => write!(f, "|"),
+            Token::BitXor => write!(f, "^"),
+            Token::BitNot => write!(f, "~"),
+            Token::Shl => write!(f, "<<"),
+            Token::Shr => write!(f, ">>"),
+            Token::Ushr => write!(f, ">>>"),
+
             Token::Assign => write!(f, "="),
 
             Token::Eq => write!(f, "=="),
@@ -1,15 +1,21 @@
 use std::fmt;
-use std::cmp::Ordering;
 
 use crate::properties::*;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     String(String),
+    Char(char),
     Boolean(bool),
     Integer(i32),
     Float(f32),
     Array(Vec<Value>),
+    Tuple(Vec<Value>),
+    Optional(Option<Box<Value>>),
+    Result(Result<Box<Value>, String>),
+
+    // index into the walker's table of open files
+    File(usize),
     None,
 }
 
@@ -39,6 +45,9 @@ impl Value {
             (Value::Float(f1), Value::Float(f2)) => {
                 f1 > f2
             },
+            (Value::Char(c1), Value::Char(c2)) => {
+                c1 > c2
+            },
             _ => {
                 panic!("Non comparable types '{}' & '{}'", self, rhs);
             }
@@ -53,6 +62,9 @@ impl Value {
             (Value::Float(f1), Value::Float(f2)) => {
                 f1 >= f2
             },
+            (Value::Char(c1), Value::Char(c2)) => {
+                c1 >= c2
+            },
             _ => {
                 panic!("Non comparable types '{}' & '{}'", self, rhs);
             }
@@ -67,6 +79,9 @@ impl Value {
             (Value::Float(f1), Value::Float(f2)) => {
                 f1 < f2
             },
+            (Value::Char(c1), Value::Char(c2)) => {
+                c1 < c2
+            },
             _ => {
                 panic!("Non comparable types '{}' & '{}'", self, rhs);
             }
@@ -81,6 +96,9 @@ impl Value {
             (Value::Float(f1), Value::Float(f2)) => {
                 f1 <= f2
             },
+            (Value::Char(c1), Value::Char(c2)) => {
+                c1 <= c2
+            },
             _ => {
                 panic!("Non comparable types '{}' & '{}'", self, rhs);
             }
@@ -101,6 +119,29 @@ impl Value {
             (Value::String(s1), Value::String(s2)) => {
                 s1 == s2
             },
+            (Value::Char(c1), Value::Char(c2)) => {
+                c1 == c2
+            },
+            (Value::File(h1), Value::File(h2)) => {
+                h1 == h2
+            },
+            (Value::Tuple(t1), Value::Tuple(t2)) => {
+                t1.iter().zip(t2).all(|(v1, v2)| v1.is_eq(v2))
+            },
+            (Value::Optional(o1), Value::Optional(o2)) => {
+                match (o1, o2) {
+                    (Some(v1), Some(v2)) => v1.is_eq(v2),
+                    (None, None) => true,
+                    _ => false,
+                }
+            },
+            (Value::Result(r1), Value::Result(r2)) => {
+                match (r1, r2) {
+                    (Ok(v1), Ok(v2)) => v1.is_eq(v2),
+                    (Err(m1), Err(m2)) => m1 == m2,
+                    _ => false,
+                }
+            },
             _ => {
                 panic!("Non comparable types '{}' & '{}'", self, rhs);
             }
@@ -121,16 +162,29 @@ impl Value {
             (Value::String(s1), Value::String(s2)) => {
                 s1 != s2
             },
+            (Value::Char(c1), Value::Char(c2)) => {
+                c1 != c2
+            },
+            (Value::File(h1), Value::File(h2)) => {
+                h1 != h2
+            },
+            (Value::Tuple(_), Value::Tuple(_)) | (Value::Optional(_), Value::Optional(_))
+                | (Value::Result(_), Value::Result(_)) => {
+                !self.is_eq(rhs)
+            },
             _ => {
                 panic!("Non comparable types '{}' & '{}'", self, rhs);
             }
         }
     }
 
-    pub fn pow_value(&self, exponent: &Value) -> Value {
+    pub fn pow_value(&self, exponent: &Value) -> Result<Value, String> {
         match (self, exponent) {
+            (Value::Integer(base), Value::Integer(exp)) if *exp < 0 => {
+                Err(format!("negative exponent in {} ** {}", base, exp))
+            },
             (Value::Integer(base), Value::Integer(exp)) => {
-                Value::Integer(i32::pow(*base, *exp as u32))
+                base.checked_pow(*exp as u32).map(Value::Integer).ok_or_else(|| overflow(*base, "**", *exp))
             },
             _ => {
                 panic!("Power unimplemented!")
@@ -138,6 +192,19 @@ impl Value {
         }
     }
 
+    // >>> treats the integer as unsigned. Like the other shifts only the low
+    // five bits of the amount are used.
+    pub fn ushr_value(&self, amount: &Value) -> Value {
+        match (self, amount) {
+            (Value::Integer(i), Value::Integer(n)) => {
+                Value::Integer((*i as u32).wrapping_shr(*n as u32) as i32)
+            },
+            _ => {
+                panic!("Logical shift not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, amount)
+            }
+        }
+    }
+
     pub fn force_int(&self) -> i32 {
         match self {
             Value::Integer(i) => *i,
@@ -146,21 +213,79 @@ impl Value {
     }
 }
 
-pub fn string_to_val(tipe: u8, string: String) -> Value {
+// What each element of 'array n' starts out as. Results and files have no
+// sensible default, so arrays of them can't be made that way.
+pub fn zero_value(tipe: u32) -> Option<Value> {
+    if tipe & RESULT != 0 {
+        None
+    } else if tipe & OPTIONAL != 0 {
+        Some(Value::Optional(None))
+    } else if tipe & ARRAY != 0 || tipe & FILE != 0 {
+        None
+    } else if tipe & TUPLE != 0 {
+        tuple_elements(tipe).into_iter().map(zero_value).collect::<Option<Vec<Value>>>().map(Value::Tuple)
+    } else if tipe & BOOLEAN != 0 {
+        Some(Value::Boolean(false))
+    } else if tipe & INTEGER != 0 {
+        Some(Value::Integer(0))
+    } else if tipe & FLOAT != 0 {
+        Some(Value::Float(0.0))
+    } else if tipe & STRING != 0 {
+        Some(Value::String(String::new()))
+    } else if tipe & CHAR != 0 {
+        Some(Value::Char('\0'))
+    } else {
+        None
+    }
+}
+
+// Shared by read and the parse_* builtins. Surrounding whitespace is
+// ignored, anything else that isn't part of the number makes it invalid.
+pub fn parse_int(s: &str, radix: u32) -> Option<i32> {
+    i32::from_str_radix(s.trim(), radix).ok()
+}
+
+pub fn parse_float(s: &str) -> Option<f32> {
+    s.trim().parse().ok()
+}
+
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn string_to_val(tipe: u32, string: String) -> Result<Value, String> {
     if tipe & STRING != 0 {
-        Value::String(string)
+        Ok(Value::String(string))
     
+    } else if tipe & CHAR != 0 {
+        let mut chars = string.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Value::Char(c)),
+            _ => Err(format!("invalid char input '{}'", string)),
+        }
+
     } else if tipe & BOOLEAN != 0 {
-        let b: bool = string.parse().unwrap();
-        Value::Boolean(b)
+        match parse_bool(&string) {
+            Some(b) => Ok(Value::Boolean(b)),
+            None => Err(format!("invalid boolean input '{}'", string)),
+        }
 
     } else if tipe & INTEGER != 0 {
-        let i: i32 = string.parse().unwrap();
-        Value::Integer(i)
+        match parse_int(&string, 10) {
+            Some(i) => Ok(Value::Integer(i)),
+            None => Err(format!("invalid integer input '{}'", string)),
+        }
 
     } else if tipe & FLOAT != 0 {
-        let f: f32 = string.parse().unwrap();
-        Value::Float(f)
+        match parse_float(&string) {
+            Some(f) => Ok(Value::Float(f)),
+            None => Err(format!("invalid float input '{}'", string)),
+        }
 
     } else {
         panic!("Not able to coerce input to a specific value");
@@ -173,45 +298,86 @@ impl fmt::Display for Value {
             Value::String(s) => {
                 write!(f, "{}", s)
             },
+            Value::Char(c) => {
+                write!(f, "{}", c)
+            },
             Value::Boolean(b) => {
                 write!(f, "{}", b)
             },
             Value::Integer(i) => {
                 write!(f, "{}", i)
             },
+            // The shortest digits that read back as the same float, whole
+            // numbers keep a '.0' so 2.0 doesn't look like an integer. From
+            // 1e16 up and below 1e-4 it switches to an exponent, 1e20 or
+            // 1.5e-7, which parse_float reads back just the same.
+            Value::Float(x) => {
+                write!(f, "{:?}", x)
+            },
             Value::Array(arr) => {
                 write!(f, "[")?;
-                if arr.len() > 0 {
+                if !arr.is_empty() {
                     write!(f, "{}", arr[0])?;
 
-                    for i in 1..=arr.len() - 1 {
-                        write!(f, ", {}", arr[i])?;
+                    for item in &arr[1..] {
+                        write!(f, ", {}", item)?;
                     }
                 }
                 write!(f, "]")
             },
-            _ => {
-                write!(f, "{:#?}", self)
-            }
+            Value::Tuple(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            },
+            Value::Optional(Some(v)) => {
+                write!(f, "some({})", v)
+            },
+            Value::Optional(None) => {
+                write!(f, "none")
+            },
+            Value::Result(Ok(v)) => {
+                write!(f, "ok({})", v)
+            },
+            Value::Result(Err(msg)) => {
+                write!(f, "err({})", msg)
+            },
+            Value::File(h) => {
+                write!(f, "<file {}>", h)
+            },
+            Value::None => {
+                write!(f, "none")
+            },
         }
     }
 }
 
-use std::ops::{Add, Sub, Mul, Div, Rem, Not, Neg};
+// Integer arithmetic that doesn't fit in an i32 is a runtime error rather
+// than wrapping around.
+fn overflow(lhs: i32, op: &str, rhs: i32) -> String {
+    format!("integer overflow in {} {} {}", lhs, op, rhs)
+}
+
+use std::ops::{Add, Sub, Mul, Div, Rem, Neg, Not, BitAnd, BitOr, BitXor, Shl, Shr};
 
 impl Add for Value {
-    type Output = Value;
+    type Output = Result<Value, String>;
 
-    fn add(self, rhs: Value) -> Value {
+    fn add(self, rhs: Value) -> Result<Value, String> {
         match (&self, &rhs) {
             (Value::Integer(i1), Value::Integer(i2)) => {
-                Value::Integer(i1 + i2)
+                i1.checked_add(*i2).map(Value::Integer).ok_or_else(|| overflow(*i1, "+", *i2))
             },
             (Value::Float(f1), Value::Float(f2)) => {
-                Value::Float(f1 + f2)
+                Ok(Value::Float(f1 + f2))
             },
             (Value::String(s1), Value::String(s2)) => {
-                Value::String(format!("{}{}", s1, s2))
+                Ok(Value::String(format!("{}{}", s1, s2)))
             },
             _ => {
                 panic!("Addition not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
@@ -221,15 +387,15 @@ impl Add for Value {
 }
 
 impl Sub for Value {
-    type Output = Value;
+    type Output = Result<Value, String>;
 
-    fn sub(self, rhs: Value) -> Value {
+    fn sub(self, rhs: Value) -> Result<Value, String> {
         match (&self, &rhs) {
             (Value::Integer(i1), Value::Integer(i2)) => {
-                Value::Integer(i1 - i2)
+                i1.checked_sub(*i2).map(Value::Integer).ok_or_else(|| overflow(*i1, "-", *i2))
             },
             (Value::Float(f1), Value::Float(f2)) => {
-                Value::Float(f1 - f2)
+                Ok(Value::Float(f1 - f2))
             },
             _ => {
                 panic!("Subtraction not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
@@ -239,15 +405,15 @@ impl Sub for Value {
 }
 
 impl Mul for Value {
-    type Output = Value;
+    type Output = Result<Value, String>;
 
-    fn mul(self, rhs: Value) -> Value {
+    fn mul(self, rhs: Value) -> Result<Value, String> {
         match (&self, &rhs) {
             (Value::Integer(i1), Value::Integer(i2)) => {
-                Value::Integer(i1 * i2)
+                i1.checked_mul(*i2).map(Value::Integer).ok_or_else(|| overflow(*i1, "*", *i2))
             },
             (Value::Float(f1), Value::Float(f2)) => {
-                Value::Float(f1 * f2)
+                Ok(Value::Float(f1 * f2))
             },
             _ => {
                 panic!("Multiplication not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
@@ -257,15 +423,18 @@ impl Mul for Value {
 }
 
 impl Div for Value {
-    type Output = Value;
+    type Output = Result<Value, String>;
 
-    fn div(self, rhs: Value) -> Value {
+    fn div(self, rhs: Value) -> Result<Value, String> {
         match (&self, &rhs) {
+            (Value::Integer(_), Value::Integer(0)) => {
+                Err("division by zero".into())
+            },
             (Value::Integer(i1), Value::Integer(i2)) => {
-                Value::Integer(i1 / i2)
+                i1.checked_div(*i2).map(Value::Integer).ok_or_else(|| overflow(*i1, "/", *i2))
             },
             (Value::Float(f1), Value::Float(f2)) => {
-                Value::Float(f1 / f2)
+                Ok(Value::Float(f1 / f2))
             },
             _ => {
                 panic!("Division not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
@@ -275,15 +444,18 @@ impl Div for Value {
 }
 
 impl Rem for Value {
-    type Output = Value;
+    type Output = Result<Value, String>;
 
-    fn rem(self, rhs: Value) -> Value {
+    fn rem(self, rhs: Value) -> Result<Value, String> {
         match (&self, &rhs) {
+            (Value::Integer(_), Value::Integer(0)) => {
+                Err("division by zero".into())
+            },
             (Value::Integer(i1), Value::Integer(i2)) => {
-                Value::Integer(i1 % i2)
+                i1.checked_rem(*i2).map(Value::Integer).ok_or_else(|| overflow(*i1, "%", *i2))
             },
             (Value::Float(f1), Value::Float(f2)) => {
-                Value::Float(f1 % f2)
+                Ok(Value::Float(f1 % f2))
             },
             _ => {
                 panic!("Modulo not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
@@ -292,14 +464,166 @@ impl Rem for Value {
     }
 }
 
-impl Neg for Value{
-    type Output = Value;
+impl Neg for Value {
+    type Output = Result<Value, String>;
 
-    fn neg(self) -> Value {
+    fn neg(self) -> Result<Value, String> {
         match &self {
-            Value::Integer(i) => Value::Integer(-i),
-            Value::Float(f) => Value::Float(-f),
+            Value::Integer(i) => i.checked_neg().map(Value::Integer).ok_or_else(|| format!("integer overflow in -({})", i)),
+            Value::Float(f) => Ok(Value::Float(-f)),
             _ => panic!("Can't negate non-numeric type '{:#?}'", self),
         }
     }
-}
\ No newline at end of file
+}
+impl Not for Value {
+    type Output = Value;
+
+    fn not(self) -> Value {
+        match &self {
+            Value::Boolean(b) => Value::Boolean(!b),
+            Value::Integer(i) => Value::Integer(!i),
+            _ => panic!("Can't invert '{:#?}'", self),
+        }
+    }
+}
+
+impl BitAnd for Value {
+    type Output = Value;
+
+    fn bitand(self, rhs: Value) -> Value {
+        match (&self, &rhs) {
+            (Value::Integer(i1), Value::Integer(i2)) => {
+                Value::Integer(i1 & i2)
+            },
+            _ => {
+                panic!("Bitwise and not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
+            },
+        }
+    }
+}
+
+impl BitOr for Value {
+    type Output = Value;
+
+    fn bitor(self, rhs: Value) -> Value {
+        match (&self, &rhs) {
+            (Value::Integer(i1), Value::Integer(i2)) => {
+                Value::Integer(i1 | i2)
+            },
+            _ => {
+                panic!("Bitwise or not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
+            },
+        }
+    }
+}
+
+impl BitXor for Value {
+    type Output = Value;
+
+    fn bitxor(self, rhs: Value) -> Value {
+        match (&self, &rhs) {
+            (Value::Integer(i1), Value::Integer(i2)) => {
+                Value::Integer(i1 ^ i2)
+            },
+            _ => {
+                panic!("Bitwise xor not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
+            },
+        }
+    }
+}
+
+impl Shl for Value {
+    type Output = Value;
+
+    fn shl(self, rhs: Value) -> Value {
+        match (&self, &rhs) {
+            (Value::Integer(i1), Value::Integer(i2)) => {
+                Value::Integer(i1.wrapping_shl(*i2 as u32))
+            },
+            _ => {
+                panic!("Left shift not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
+            },
+        }
+    }
+}
+
+// >> copies the sign bit in, see ushr_value for the logical shift.
+impl Shr for Value {
+    type Output = Value;
+
+    fn shr(self, rhs: Value) -> Value {
+        match (&self, &rhs) {
+            (Value::Integer(i1), Value::Integer(i2)) => {
+                Value::Integer(i1.wrapping_shr(*i2 as u32))
+            },
+            _ => {
+                panic!("Right shift not allowed for '{:#?}' and {:#?}. Typechecking failed you :(", self, rhs)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn float(x: f32) -> String {
+        Value::Float(x).to_string()
+    }
+
+    #[test]
+    fn whole_floats_keep_a_decimal_point() {
+        assert_eq!(float(2.0), "2.0");
+        assert_eq!(float(-3.0), "-3.0");
+        assert_eq!(float(-0.0), "-0.0");
+        assert_eq!(float(1e15), "1000000000000000.0");
+    }
+
+    #[test]
+    fn fractions_use_the_shortest_digits() {
+        assert_eq!(float(0.5), "0.5");
+        assert_eq!(float(0.1 + 0.2), "0.3");
+        assert_eq!(float(123456.79), "123456.79");
+        assert_eq!(float(1e-4), "0.0001");
+    }
+
+    #[test]
+    fn large_and_small_floats_use_an_exponent() {
+        assert_eq!(float(1e16), "1e16");
+        assert_eq!(float(1e20), "1e20");
+        assert_eq!(float(f32::MAX), "3.4028235e38");
+        assert_eq!(float(1e-5), "1e-5");
+        assert_eq!(float(1.5e-7), "1.5e-7");
+        assert_eq!(float(f32::INFINITY), "inf");
+    }
+
+    #[test]
+    fn floats_read_back_the_same() {
+        for x in [0.1, 2.0, 1e20, 1.5e-7, 123456.79, f32::MAX, f32::MIN_POSITIVE] {
+            assert_eq!(parse_float(&float(x)), Some(x));
+        }
+    }
+
+    #[test]
+    fn every_variant() {
+        let values: Vec<(Value, &str)> = vec![
+            (Value::String("hi".into()), "hi"),
+            (Value::Char('é'), "é"),
+            (Value::Boolean(true), "true"),
+            (Value::Integer(-7), "-7"),
+            (Value::Array(vec![Value::Integer(1), Value::Float(2.0)]), "[1, 2.0]"),
+            (Value::Array(vec![]), "[]"),
+            (Value::Tuple(vec![Value::Integer(1), Value::String("a".into())]), "(1, a)"),
+            (Value::Optional(Some(Box::new(Value::Integer(3)))), "some(3)"),
+            (Value::Optional(None), "none"),
+            (Value::Result(Ok(Box::new(Value::Float(0.5)))), "ok(0.5)"),
+            (Value::Result(Err("bad".into())), "err(bad)"),
+            (Value::File(2), "<file 2>"),
+            (Value::None, "none"),
+        ];
+
+        for (value, text) in values {
+            assert_eq!(value.to_string(), text);
+        }
+    }
+}
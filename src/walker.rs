@@ -1,19 +1,49 @@
 use std::io;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use crate::ast::*;
 use crate::value::*;
 use crate::token::Token;
-
-#[derive(Debug, Clone)]
+use crate::properties::{NONE, ARRAY, OPTIONAL, RESULT};
+use crate::builtins;
+use crate::error;
+use crate::input::Input;
+use crate::files::Files;
+use crate::random::Rng;
+
+#[derive(Debug)]
 pub struct Walker {
     // TODO: figure out if this is a sustainable way of storing top level nodes.
     // The idea is that each top-level node takes n arguments off the stack.
-    top_level: HashMap<String, (u32, Box<ASTNode>)>,
+    top_level: HashMap<String, (u32, bool, Box<ASTNode>)>,
+
+    // name and body of every test block, in source order
+    tests: Vec<(String, Box<ASTNode>)>,
 
     val_stack: Vec<Value>,
 
     local_variables: Vec<Vec<Value>>,
+
+    // stdin, shared by read, readline() and eof()
+    input: Input,
+
+    files: Files,
+
+    // now_ms() and monotonic_ns() count from here
+    started: Instant,
+
+    // every test starts over from the same seed
+    seed: u64,
+    rng: Rng,
+
+    // Set by a runtime error, unwinds every call up to walk().
+    error: Option<String>,
+
+    // Set by exit(), unwinds the same way without being an error.
+    exit_code: Option<i32>,
 }
 
 // XXX:
@@ -22,55 +52,206 @@ pub struct Walker {
 // If you contribute, you daren't cause more of a mess.
 impl Walker {
     pub fn new(args: Vec<String>, top_level: ASTNode) -> Walker {
-        let converted_args: Vec<Value>;
-        let arg_count: i32;
+        let seed: u64 = Rng::clock_seed();
 
-        converted_args = args.iter().map(|v| Value::String(v.clone())).collect();
-        arg_count = converted_args.len() as i32;
+        let converted_args: Vec<Value> = args.iter().map(|v| Value::String(v.clone())).collect();
+        let arg_count: i32 = converted_args.len() as i32;
 
-        let mut symboltable: HashMap<String, (u32, Box<ASTNode>)> = HashMap::new();
+        let mut symboltable: HashMap<String, (u32, bool, Box<ASTNode>)> = HashMap::new();
+        let mut tests: Vec<(String, Box<ASTNode>)> = vec![];
 
-        if let ASTNode::Toplevel{ funcdefs } = top_level {
+        if let ASTNode::Toplevel{ funcdefs, tests: test_defs } = top_level {
             for fdef in funcdefs {
-                if let ASTNode::Funcdef {name, params, ret_type: _, body} = *fdef {
-                    symboltable.insert(name, (params.len() as u32, body));
+                if let ASTNode::Funcdef {name, params, ret_type: _, variadic, source: _, loc: _, body} = *fdef {
+                    symboltable.insert(name, (params.len() as u32, variadic, body));
                 } else {
                     panic!("Funcdef node was in-fact not a funcdef node :(");
                 }
             }
+
+            for test in test_defs {
+                if let ASTNode::Funcdef {name, params: _, ret_type: _, variadic: _, source: _, loc: _, body} = *test {
+                    tests.push((name, body));
+                }
+            }
         } else {
             panic!("Top-level node was in-fact not a top level node :(");
         }
 
         Walker {
             top_level: symboltable,
+            tests,
             val_stack: vec![Value::Integer(arg_count), Value::Array(converted_args)],
             local_variables: vec![vec![]],
+            input: Input::new(),
+            files: Files::new(),
+            started: Instant::now(),
+            seed,
+            rng: Rng::new(seed),
+            error: None,
+            exit_code: None,
         }
     }
 
     // since std::process::exit expects an i32
+    // The parser has made sure main exists and takes nothing, the arguments
+    // or both argc and the arguments.
     pub fn walk(&mut self) -> i32 {
-        let start: &(u32, Box<ASTNode>) = self.top_level.get("main").expect("Programs are required to have a main function.");
+        let start: &(u32, bool, Box<ASTNode>) = &self.top_level["main"];
 
         let mut arg_slice = self.val_stack.split_off(self.val_stack.len() - (start.0 as usize) );
+        self.val_stack.clear();
         let size: usize = self.local_variables.len();
         self.local_variables[size - 1].append(&mut arg_slice);
 
-        self.visit_node(start.1.clone());
+        self.visit_node(start.2.clone());
+        io::stdout().flush().expect("Flushing stdout failed");
+
+        if let Some(msg) = &self.error {
+            eprintln!("rick: runtime error: {}", msg);
+            return 1;
+        }
+        if let Some(code) = self.exit_code {
+            return code;
+        }
+
+        // a procedure main leaves nothing behind
+        let result = match self.val_stack.pop() {
+            Some(result) => result,
+            None => return 0,
+        };
+
+        match result {
+            Value::Integer(i) => i,
+            Value::Result(Ok(inner)) if matches!(*inner, Value::Integer(_)) => inner.force_int(),
+            Value::Result(Err(msg)) => {
+                eprintln!("rick: error: {}", msg);
+                1
+            },
+            _ => {
+                panic!("Non-integer type returned upon exit :(");
+            },
+        }
+    }
 
-        let result = self.val_stack.pop().expect("Empty stack upon exit :(");
+    // Runs every test block on a clean stack and frame. A runtime error, a
+    // failed assert included, unwinds out of the test and marks it as failed.
+    pub fn run_tests(&mut self) -> i32 {
+        let mut failed: usize = 0;
 
-        if let Value::Integer(i) = result {
-            return i;
-        } else {
-            panic!("Non-integer type returned upon exit :(");
+        println!("running {} test{}", self.tests.len(), if self.tests.len() == 1 { "" } else { "s" });
+
+        // the message is reported with the test instead
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+
+        for (name, body) in self.tests.clone() {
+            self.val_stack.clear();
+            self.local_variables = vec![vec![]];
+            self.error = None;
+            self.exit_code = None;
+            self.rng = Rng::new(self.seed);
+
+            // a bug in the interpreter fails the test it happened in, not the whole run
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.visit_node(body))) {
+                let msg: String = match payload.downcast_ref::<&str>() {
+                    Some(s) => s.to_string(),
+                    None => payload.downcast_ref::<String>().cloned().unwrap_or_default(),
+                };
+
+                self.error = Some(format!("interpreter panicked: {}", msg));
+            }
+            io::stdout().flush().expect("Flushing stdout failed");
+
+            // exit(0) ends a test early without failing it
+            match (self.error.take(), self.exit_code.take()) {
+                (Some(msg), _) => {
+                    println!("test {} ... FAILED", name);
+                    println!("    {}", msg);
+                    failed += 1;
+                },
+                (None, Some(code)) if code != 0 => {
+                    println!("test {} ... FAILED", name);
+                    println!("    exited with {}", code);
+                    failed += 1;
+                },
+                _ => println!("test {} ... ok", name),
+            }
+        }
+
+        panic::set_hook(hook);
+
+        println!();
+        println!("test result: {}. {} passed; {} failed", if failed == 0 { "ok" } else { "FAILED" },
+            self.tests.len() - failed, failed);
+
+        if failed == 0 { 0 } else { 1 }
+    }
+
+    pub fn input(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn files(&mut self) -> &mut Files {
+        &mut self.files
+    }
+
+    // Makes rand_int() and rand_float() repeat the same numbers every run.
+    pub fn seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    // Ends the program once the current builtin call returns.
+    pub fn exit(&mut self, code: i32) {
+        self.exit_code = Some(code);
+    }
+
+    // A runtime error or exit() is on its way up to walk().
+    fn stopping(&self) -> bool {
+        self.error.is_some() || self.exit_code.is_some()
+    }
+
+    // Next word of input as a value of the given type.
+    fn read_value(&mut self, tipe: u32) -> Result<Value, String> {
+        match self.input.token() {
+            Some(token) => string_to_val(tipe, token),
+            None => Err("unexpected end of input".into()),
+        }
+    }
+
+    // Bad input is returned as an error by read(...)? and ends the program otherwise.
+    fn read_failed(&mut self, msg: String, propagate: bool) -> bool {
+        if propagate {
+            self.val_stack.push(Value::Result(Err(msg)));
+            return true;
         }
+
+        self.runtime_error(msg)
+    }
+
+    // Raises a runtime error and starts unwinding, the return value is meant
+    // to be handed straight back from visit_node.
+    fn runtime_error(&mut self, msg: String) -> bool {
+        self.error = Some(msg);
+
+        true
     }
 
+    // Returns true when the current function is returning, which also covers
+    // a '?' bailing out halfway through an expression and runtime errors.
+    // Expressions have to pass that on without touching the stack any further.
     fn visit_node(&mut self, node: Box<ASTNode>) -> bool {
         match *node {
-            ASTNode::Block { statements } => {
+            ASTNode::Block { statements, locs: _ } => {
                 // For "de-allocating" if there are any vardefs in statements
                 let idx: usize = self.local_variables.len();
                 let initial_size: usize = self.local_variables[idx - 1].len();
@@ -84,48 +265,117 @@ impl Walker {
 
                 self.local_variables[idx - 1].truncate(initial_size);
             },
-            ASTNode::VarDef { tipe: _, names } => {
+            ASTNode::VarDef { vars } => {
                 let len: usize = self.local_variables.len();
-                for _ in names {
-                    self.local_variables[len - 1].push(Value::None);
+                for (_, tipe, init, _) in vars {
+                    if let Some(init) = init {
+                        if self.visit_node(init) {
+                            return true;
+                        }
+                        let val: Value = self.val_stack.pop().unwrap();
+                        self.local_variables[len - 1].push(val);
+                    } else if tipe & (OPTIONAL | RESULT) == OPTIONAL {
+                        self.local_variables[len - 1].push(Value::Optional(None));
+                    } else {
+                        self.local_variables[len - 1].push(Value::None);
+                    }
                 }
             },
             ASTNode::Call { name, args } => {
                 let mut new_scope: Vec<Value> = vec![];
+                let stack_size: usize = self.val_stack.len();
 
                 for arg in args {
-                    self.visit_node(arg);
+                    if self.visit_node(arg) {
+                        return true;
+                    }
                     
                     new_scope.push(self.val_stack.pop().unwrap());
                 }
+
+                if let Some(builtin) = builtins::lookup(&name) {
+                    match (builtin.call)(self, new_scope) {
+                        Ok(val) if builtin.ret_type != NONE => self.val_stack.push(val),
+                        Ok(_) => {},
+                        Err(msg) => return self.runtime_error(msg),
+                    }
+
+                    return self.stopping();
+                }
+
+                let (param_count, variadic, body) = self.top_level.get(&name).unwrap().clone();
+
+                if variadic {
+                    let rest: Vec<Value> = new_scope.split_off(param_count as usize - 1);
+                    new_scope.push(Value::Array(rest));
+                }
                 self.local_variables.push(new_scope);
 
-                let (_, body) = self.top_level.get(&name).unwrap();
-                self.visit_node(body.clone());
+                self.visit_node(body);
 
                 self.local_variables.pop();
+
+                if self.stopping() {
+                    return true;
+                }
+
+                // A '?' can return while parts of an expression are still on the stack.
+                if self.val_stack.len() > stack_size + 1 {
+                    let ret: Value = self.val_stack.pop().unwrap();
+                    self.val_stack.truncate(stack_size);
+                    self.val_stack.push(ret);
+                }
+            },
+            ASTNode::Inlined { name: _, base, args, body } => {
+                let mut values: Vec<Value> = vec![];
+
+                for arg in args {
+                    if self.visit_node(arg) {
+                        return true;
+                    }
+
+                    values.push(self.val_stack.pop().unwrap());
+                }
+
+                let idx: usize = self.local_variables.len();
+                self.local_variables[idx - 1].append(&mut values);
+
+                let returned: bool = self.visit_node(body);
+                self.local_variables[idx - 1].truncate(base as usize);
+
+                return returned;
             },
-            ASTNode::Let { offset, index, is_array, rhs } => {
-                self.visit_node(rhs);
+            ASTNode::Let { offset, index, fill, rhs, loc: _ } => {
+                if self.visit_node(rhs) {
+                    return true;
+                }
 
-                if is_array {
+                if let Some(fill) = fill {
                     let cap: i32 = self.val_stack.pop().unwrap().force_int();
-                    let mut arr: Vec<Value> = Vec::with_capacity(cap as usize);
-                    for i in 0..(cap as u32) {
-                        arr.push(Value::None);
+                    if cap < 0 {
+                        return self.runtime_error(format!("array size {} is negative", cap));
                     }
-                    
+                    let arr: Vec<Value> = vec![fill; cap as usize];
+
                     let idx: usize = self.local_variables.len();
                     self.local_variables[idx - 1][offset as usize] = Value::Array(arr);
                 } else {
-                    if index.is_some() {
-                        self.visit_node(index.unwrap());
+                    if let Some(index) = index {
+                        if self.visit_node(index) {
+                            return true;
+                        }
                         let idx: i32 = self.val_stack.pop().unwrap().force_int();
+                        let val: Value = self.val_stack.pop().unwrap();
                         let j: usize = self.local_variables.len();
-                        let arr: Value = self.local_variables[j - 1][offset as usize].clone();
-                        if let Value::Array(mut inner) = arr {
-                            inner[idx as usize] = self.val_stack.pop().unwrap();
-                            self.local_variables[j - 1][offset as usize] = Value::Array(inner);
+
+                        if let Value::Array(inner) = &mut self.local_variables[j - 1][offset as usize] {
+                            match position(idx, inner.len()) {
+                                Some(i) => inner[i] = val,
+                                None => {
+                                    let len: usize = inner.len();
+                                    return self.runtime_error(out_of_range(idx, len, "array"));
+                                },
+                            }
                         } else {
                             panic!("Typechecking fail");
                         }
@@ -136,154 +386,317 @@ impl Walker {
                     }
                 }
             },
-            ASTNode::Read { offset, tipe, index } => {
+            ASTNode::Destructure { offsets, rhs, loc: _ } => {
+                if self.visit_node(rhs) {
+                    return true;
+                }
+
+                if let Value::Tuple(items) = self.val_stack.pop().unwrap() {
+                    let idx: usize = self.local_variables.len();
 
+                    for (offset, item) in offsets.iter().zip(items) {
+                        if let Some(offset) = offset {
+                            self.local_variables[idx - 1][*offset as usize] = item;
+                        }
+                    }
+                } else {
+                    panic!("Typechecking fail");
+                }
+            },
+            ASTNode::Read { targets, propagate } => {
                 let idx: usize = self.local_variables.len();
-                let val: Value = self.local_variables[idx - 1][offset as usize].clone();
-                
-                let mut buffer: String = String::new();
-                let stdin = io::stdin();
-                stdin.read_line(&mut buffer).expect("Read failed");
-                buffer = buffer.trim().to_string();
 
-                let val_to_store: Value = string_to_val(tipe, buffer);
+                for (offset, tipe, index, _) in targets {
+                    let slot: usize = offset as usize;
+
+                    // an element, every element of an array or a plain variable
+                    let positions: Vec<Option<usize>> = if let Some(index) = index {
+                        if self.visit_node(index) {
+                            return true;
+                        }
 
-                if index.is_some() {
-                    self.visit_node(index.unwrap());
+                        let i: i32 = self.val_stack.pop().unwrap().force_int();
+                        let len: usize = match &self.local_variables[idx - 1][slot] {
+                            Value::Array(items) => items.len(),
+                            _ => panic!("Typecheck fail! Should be an array..."),
+                        };
 
-                    if let Value::Array(mut inner) = val {
-                        let store_idx: usize = self.val_stack.pop().unwrap().force_int() as usize;
-                        inner[store_idx] = val_to_store;
-                        self.local_variables[idx - 1][offset as usize] = Value::Array(inner);
+                        match position(i, len) {
+                            Some(i) => vec![Some(i)],
+                            None => return self.runtime_error(out_of_range(i, len, "array")),
+                        }
+                    } else if tipe & ARRAY != 0 {
+                        match &self.local_variables[idx - 1][slot] {
+                            Value::Array(items) => (0..items.len()).map(Some).collect(),
+                            _ => panic!("Typecheck fail! Should be an array..."),
+                        }
                     } else {
-                        panic!("Typecheck fail! Should be an array...");
+                        vec![None]
+                    };
+
+                    for position in positions {
+                        let val_to_store: Value = match self.read_value(tipe) {
+                            Ok(val) => val,
+                            Err(msg) => return self.read_failed(msg, propagate),
+                        };
+
+                        match (position, &mut self.local_variables[idx - 1][slot]) {
+                            (Some(i), Value::Array(inner)) => inner[i] = val_to_store,
+                            (Some(_), _) => panic!("Typecheck fail! Should be an array..."),
+                            (None, var) => *var = val_to_store,
+                        }
                     }
-                } else {
-                    self.local_variables[idx - 1][offset as usize] = val_to_store;
                 }
             },
             ASTNode::If { branches, else_case } => {
                 for (cond, body) in branches {
-                    self.visit_node(cond);
+                    if self.visit_node(cond) {
+                        return true;
+                    }
 
                     if self.val_stack.pop().unwrap().is_truthy() {
-                        if self.visit_node(body) {
-                            return true;
-                        } else {
-                            return false;
-                        }
+                        return self.visit_node(body)
                     }
                 }
-                if else_case.is_some() {
-                    return self.visit_node(else_case.unwrap());
+                if let Some(else_case) = else_case {
+                    return self.visit_node(else_case);
                 }
             },  
+            ASTNode::IfLet { offset, value, then_case, else_case } => {
+                if self.visit_node(value) {
+                    return true;
+                }
+
+                if let Value::Optional(Some(inner)) = self.val_stack.pop().unwrap() {
+                    let idx: usize = self.local_variables.len();
+                    self.local_variables[idx - 1].push(*inner);
+
+                    let returned: bool = self.visit_node(then_case);
+                    self.local_variables[idx - 1].truncate(offset as usize);
+
+                    return returned;
+                } else if let Some(else_case) = else_case {
+                    return self.visit_node(else_case);
+                }
+            },
             ASTNode::While { condition, statement } => {
                 let mut res: bool;
 
-                self.visit_node(condition.clone());
+                if self.visit_node(condition.clone()) {
+                    return true;
+                }
                 res = self.val_stack.pop().unwrap().is_truthy();
 
                 while res {
                     if self.visit_node(statement.clone()) {
                         return true;
                     }
-                    self.visit_node(condition.clone());
+                    if self.visit_node(condition.clone()) {
+                        return true;
+                    }
                     res = self.val_stack.pop().unwrap().is_truthy();
                 } 
             },
             ASTNode::Return { expr } => {
-                if expr.is_some() {
-                    self.visit_node(expr.unwrap());
+                if let Some(expr) = expr {
+                    self.visit_node(expr);
                 }
                 return true;
             },
-            ASTNode::Print { items } => {
+            ASTNode::Assert { condition, message, source, loc } => {
+                if self.visit_node(condition) {
+                    return true;
+                }
+
+                if !self.val_stack.pop().unwrap().is_truthy() {
+                    let mut msg: String = format!("{}: {}:{} assertion failed", error::source_name_of(source), loc.0, loc.1);
+
+                    // only worked out when it is needed
+                    if let Some(message) = message {
+                        if self.visit_node(message) {
+                            return true;
+                        }
+                        msg = format!("{}: {}", msg, self.val_stack.pop().unwrap());
+                    }
+
+                    return self.runtime_error(msg);
+                }
+            },
+            ASTNode::Print { items, stderr } => {
+                // keeps the two streams in order when they end up in the same place
+                if stderr {
+                    io::stdout().flush().expect("Flushing stdout failed");
+                }
+
                 for item in items {
-                    self.visit_node(item);
-                    print!("{}", self.val_stack.pop().unwrap());
+                    if self.visit_node(item) {
+                        return true;
+                    }
+
+                    if stderr {
+                        eprint!("{}", self.val_stack.pop().unwrap());
+                    } else {
+                        print!("{}", self.val_stack.pop().unwrap());
+                    }
                 }
             },
-            ASTNode::GetVar { name: _, offset } => {
+            ASTNode::GetVar { name: _, offset, loc: _ } => {
                 let len: usize = self.local_variables.len();
                 let val = self.local_variables[len - 1][offset as usize].clone();
                 self.val_stack.push(val);
             },
-            ASTNode::GetIndex { offset, idx } => {
+            ASTNode::GetIndex { offset, idx, loc: _ } => {
                 let len = self.local_variables.len();
                 let arr = self.local_variables[len - 1][offset as usize].clone();
 
-                self.visit_node(idx);
+                if self.visit_node(idx) {
+                    return true;
+                }
+
+                let true_idx: i32 = self.val_stack.pop().unwrap().force_int();
 
-                let true_idx: usize = self.val_stack.pop().unwrap().force_int() as usize;
-                
                 if let Value::Array(inner) = arr {
-                    self.val_stack.push(inner[true_idx].clone());
+                    match position(true_idx, inner.len()) {
+                        Some(i) => self.val_stack.push(inner[i].clone()),
+                        None => return self.runtime_error(out_of_range(true_idx, inner.len(), "array")),
+                    }
+                } else if let Value::String(s) = arr {
+                    match position(true_idx, usize::MAX).and_then(|i| s.chars().nth(i)) {
+                        Some(c) => self.val_stack.push(Value::Char(c)),
+                        None => return self.runtime_error(out_of_range(true_idx, s.chars().count(), "string")),
+                    }
                 } else {
                     panic!("Somehow indexed a non-array");
                 }
             },
             // TODO: BinaryOp looks messy
             ASTNode::BinaryOp { lhs, op, rhs } => {
-                self.visit_node(lhs);
-                self.visit_node(rhs);
+                if self.visit_node(lhs) || self.visit_node(rhs) {
+                    return true;
+                }
                 
                 let rval = self.val_stack.pop().unwrap();
                 let lval = self.val_stack.pop().unwrap();
 
-                match op {
-                    Token::Add => {
-                        self.val_stack.push(lval + rval);
-                    },
-                    Token::Sub => {
-                        self.val_stack.push(lval - rval);
-                    },
-                    Token::Mul => {
-                        self.val_stack.push(lval * rval);
-                    },
-                    Token::Div => {
-                        self.val_stack.push(lval / rval);
-                    },
-                    Token::Mod => {
-                        self.val_stack.push(lval % rval);
-                    },
-                    Token::Gt => {
-                        self.val_stack.push(Value::Boolean(lval.is_gt(&rval)));
-                    },
-                    Token::Gte => {
-                        self.val_stack.push(Value::Boolean(lval.is_gte(&rval)));
-                    },
-                    Token::Lt => {
-                        self.val_stack.push(Value::Boolean(lval.is_lt(&rval)));
-                    },
-                    Token::Lte => {
-                        self.val_stack.push(Value::Boolean(lval.is_lte(&rval)));
-                    },
-                    Token::Eq => {
-                        self.val_stack.push(Value::Boolean(lval.is_eq(&rval)));
-                    },
-                    Token::Neq => {
-                        self.val_stack.push(Value::Boolean(lval.is_neq(&rval)));
-                    },
-                    Token::Pow => {
-                        self.val_stack.push(lval.pow_value(&rval));
-                    },
+                let result: Result<Value, String> = match op {
+                    Token::Add => lval + rval,
+                    Token::Sub => lval - rval,
+                    Token::Mul => lval * rval,
+                    Token::Div => lval / rval,
+                    Token::Mod => lval % rval,
+                    Token::Gt => Ok(Value::Boolean(lval.is_gt(&rval))),
+                    Token::Gte => Ok(Value::Boolean(lval.is_gte(&rval))),
+                    Token::Lt => Ok(Value::Boolean(lval.is_lt(&rval))),
+                    Token::Lte => Ok(Value::Boolean(lval.is_lte(&rval))),
+                    Token::Eq => Ok(Value::Boolean(lval.is_eq(&rval))),
+                    Token::Neq => Ok(Value::Boolean(lval.is_neq(&rval))),
+                    Token::Pow => lval.pow_value(&rval),
+                    Token::BitAnd => Ok(lval & rval),
+                    Token::BitOr => Ok(lval | rval),
+                    Token::BitXor => Ok(lval ^ rval),
+                    Token::Shl => Ok(lval << rval),
+                    Token::Shr => Ok(lval >> rval),
+                    Token::Ushr => Ok(lval.ushr_value(&rval)),
                     _ => {
                         unimplemented!("Binary operator '{}'", op);
                     }
+                };
+
+                match result {
+                    Ok(val) => self.val_stack.push(val),
+                    Err(msg) => return self.runtime_error(msg),
                 }
             },
             ASTNode::UnaryOp { op, value } => {
-                self.visit_node(value);
+                if self.visit_node(value) {
+                    return true;
+                }
                 let val = self.val_stack.pop().unwrap();
 
                 match &op {
-                    Token::Sub => {
-                        self.val_stack.push(-val);
+                    Token::Sub => match -val {
+                        Ok(val) => self.val_stack.push(val),
+                        Err(msg) => return self.runtime_error(msg),
+                    },
+                    Token::Negate | Token::BitNot => {
+                        self.val_stack.push(!val);
                     },
                     _ => panic!("Not a unary op '{}'", op),
                 }
             },
+            ASTNode::Some { value } => {
+                if self.visit_node(value) {
+                    return true;
+                }
+
+                let inner: Value = self.val_stack.pop().unwrap();
+                self.val_stack.push(Value::Optional(Some(Box::new(inner))));
+            },
+            ASTNode::Ok { value } => {
+                if self.visit_node(value) {
+                    return true;
+                }
+
+                let inner: Value = self.val_stack.pop().unwrap();
+                self.val_stack.push(Value::Result(Ok(Box::new(inner))));
+            },
+            ASTNode::Err { message } => {
+                if self.visit_node(message) {
+                    return true;
+                }
+
+                let msg: String = self.val_stack.pop().unwrap().to_string();
+                self.val_stack.push(Value::Result(Err(msg)));
+            },
+            ASTNode::Discard { value } => {
+                if self.visit_node(value) {
+                    return true;
+                }
+
+                self.val_stack.pop();
+            },
+            ASTNode::Try { value, propagate } => {
+                if self.visit_node(value) {
+                    return true;
+                }
+
+                match self.val_stack.pop().unwrap() {
+                    Value::Result(Ok(inner)) => {
+                        self.val_stack.push(*inner);
+                    },
+                    Value::Result(Err(msg)) if propagate => {
+                        self.val_stack.push(Value::Result(Err(msg)));
+                        return true;
+                    },
+                    Value::Result(Err(msg)) => {
+                        return self.runtime_error(msg);
+                    },
+                    _ => panic!("Typechecking fail"),
+                }
+            },
+            ASTNode::Tuple { items } => {
+                let mut values: Vec<Value> = Vec::with_capacity(items.len());
+
+                for item in items {
+                    if self.visit_node(item) {
+                        return true;
+                    }
+                    values.push(self.val_stack.pop().unwrap());
+                }
+
+                self.val_stack.push(Value::Tuple(values));
+            },
+            ASTNode::Conditional { condition, then_value, else_value } => {
+                if self.visit_node(condition) {
+                    return true;
+                }
+
+                if self.val_stack.pop().unwrap().is_truthy() {
+                    return self.visit_node(then_value);
+                } else {
+                    return self.visit_node(else_value);
+                }
+            },
             ASTNode::Value { val } => {
                 self.val_stack.push(val);
             },
@@ -294,4 +707,12 @@ impl Walker {
 
         false
     }
-}
\ No newline at end of file
+}
+// Where idx points in something of length len, None if it's out of range.
+fn position(idx: i32, len: usize) -> Option<usize> {
+    usize::try_from(idx).ok().filter(|i| *i < len)
+}
+
+fn out_of_range(idx: i32, len: usize, what: &str) -> String {
+    format!("index {} out of range for {} of length {}", idx, what, len)
+}
@@ -0,0 +1,62 @@
+// Runs rick on every case in tests/golden and compares the result with the
+// recorded one. A case is made of:
+//   name.args      the command line, relative to the crate root
+//   name.in        what goes to stdin, empty if it is missing
+//   name.expected  the exit code, stdout and stderr
+// Set RICK_BLESS=1 to write the current output as the expected one.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+fn run_case(args: &Path) -> String {
+    let line: String = fs::read_to_string(args).unwrap();
+    let input: String = fs::read_to_string(args.with_extension("in")).unwrap_or_default();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rick"))
+        .args(line.split_whitespace())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    format!("exit: {}\n--- stdout\n{}--- stderr\n{}",
+        output.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr))
+}
+
+#[test]
+fn golden() {
+    let mut cases: Vec<PathBuf> = fs::read_dir("tests/golden").unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "args"))
+        .collect();
+    cases.sort();
+
+    let bless: bool = std::env::var_os("RICK_BLESS").is_some();
+    let mut failed: Vec<String> = vec![];
+
+    for case in &cases {
+        let actual: String = run_case(case);
+        let expected_path: PathBuf = case.with_extension("expected");
+
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        let expected: String = fs::read_to_string(&expected_path).unwrap_or_default();
+        if actual != expected {
+            failed.push(format!("{}\n=== expected\n{}=== actual\n{}", case.display(), expected, actual));
+        }
+    }
+
+    assert!(!cases.is_empty(), "no cases in tests/golden");
+    assert!(failed.is_empty(), "{} golden case(s) differ:\n\n{}", failed.len(), failed.join("\n"));
+}